@@ -28,7 +28,12 @@ fn main() {
     let app = Application::with_options(&opt);
     let (width, height) = app.size();
     let mname = args().nth(1).expect("missing args");
-    let model = import_scene(&fs::read(&mname).expect("cannot read file"), width, height);
+    let model = import_scene(
+        app.gl(),
+        &fs::read(&mname).expect("cannot read file"),
+        width,
+        height,
+    );
     let frame_index = 0;
     let result = BenchOutput {
         name: mname,
@@ -0,0 +1,76 @@
+extern crate glad_gles2;
+extern crate glutin;
+extern crate pretty_env_logger;
+extern crate scarlet;
+
+use glad_gles2::gl;
+use glutin::dpi::LogicalSize;
+use glutin::event::{Event, WindowEvent};
+use glutin::event_loop::{ControlFlow, EventLoop};
+use glutin::window::WindowBuilder;
+use glutin::{ContextBuilder, GlProfile, GlRequest};
+use scarlet::{import_scene_from_path, GlContext};
+use std::env::args;
+
+fn main() {
+    pretty_env_logger::init();
+    // The host owns the window, the context and the event loop
+    let event_loop = EventLoop::new();
+    let window_builder = WindowBuilder::new()
+        .with_title("Scarlet embedded viewer")
+        .with_inner_size(LogicalSize::new(1280, 720));
+    let context = ContextBuilder::new()
+        .with_gl(GlRequest::GlThenGles {
+            opengl_version: (3, 3),
+            opengles_version: (3, 0),
+        })
+        .with_gl_profile(GlProfile::Core)
+        .build_windowed(window_builder, &event_loop)
+        .expect("Cannot create OpenGL context");
+    let context = unsafe {
+        context
+            .make_current()
+            .expect("Cannot make OpenGL context current")
+    };
+    let gl = unsafe { GlContext::new(|s| context.get_proc_address(s), false) };
+    let size = context.window().inner_size();
+    let mut model = Some(import_scene_from_path(
+        &gl,
+        args().nth(1).expect("missing args"),
+        size.width,
+        size.height,
+    ));
+    event_loop.run(move |ev, _, cf| {
+        *cf = ControlFlow::Poll;
+        match ev {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                // Free the GPU resources while the context is still alive
+                model.take();
+                gl.invalidate();
+                *cf = ControlFlow::Exit;
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                context.resize(size);
+                if let Some(model) = model.as_mut() {
+                    model.resize(size.width, size.height);
+                }
+            }
+            Event::MainEventsCleared => {
+                if let Some(model) = model.as_mut() {
+                    model.draw("final", true);
+                    unsafe {
+                        gl::Flush();
+                    }
+                    context.swap_buffers().expect("Cannot swap buffers");
+                }
+            }
+            _ => {}
+        }
+    });
+}
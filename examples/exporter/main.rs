@@ -9,10 +9,9 @@ extern crate pretty_env_logger;
 
 use glad_gles2::gl;
 use glutin::event::{Event, StartCause, WindowEvent};
-use scarlet::{import_scene, Application, ApplicationAction, ApplicationOptions};
+use scarlet::{import_scene_from_path, Application, ApplicationAction, ApplicationOptions};
 use std::env::args;
 use std::ffi::c_void;
-use std::fs;
 use std::fs::File;
 use std::io::BufWriter;
 use std::path::Path;
@@ -25,8 +24,9 @@ fn main() {
     let app = Application::with_options(&opt);
     let (width, height) = app.size();
     let framebuffer = vec![0_u8; width as usize * height as usize * 3];
-    let model = import_scene(
-        &fs::read(args().nth(1).expect("missing args")).expect("cannot read file"),
+    let model = import_scene_from_path(
+        app.gl(),
+        args().nth(1).expect("missing args"),
         width,
         height,
     );
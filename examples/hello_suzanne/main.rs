@@ -18,7 +18,7 @@ fn main() {
     opt.fps = 60.0;
     let app = Application::with_options(&opt);
     let (width, height) = app.size();
-    let suzanne = import_scene(include_bytes!("suzanne.glb"), width, height);
+    let suzanne = import_scene(app.gl(), include_bytes!("suzanne.glb"), width, height);
     let sm = suzanne.get_node("Suzanne").expect("No monkey!");
     app.run((suzanne, sm), move |up, ev| {
         let suzanne = &mut up.0;
@@ -58,7 +58,7 @@ fn main() {
         gl::BindBuffer(gl::GL_ARRAY_BUFFER, 0);
         gl::BindVertexArray(0);
     }
-    let mut shader = Shader::new();
+    let mut shader = Shader::new(app.gl());
     shader.attach(include_str!("shader.vert"), ShaderType::Vertex);
     shader.attach(include_str!("shader.frag"), ShaderType::Fragment);
     shader.compile();
@@ -71,7 +71,11 @@ fn main() {
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
-            } => ApplicationAction::Quit,
+            } => unsafe {
+                gl::DeleteVertexArrays(1, &vao);
+                gl::DeleteBuffers(1, &vbo);
+                ApplicationAction::Quit
+            },
             Event::RedrawRequested { .. }
             | Event::NewEvents(StartCause::ResumeTimeReached { .. }) => unsafe {
                 gl::Clear(gl::GL_COLOR_BUFFER_BIT);
@@ -85,9 +89,4 @@ fn main() {
             _ => ApplicationAction::Nothing,
         }
     });
-    #[allow(unreachable_code)]
-    unsafe {
-        gl::DeleteVertexArrays(1, &mut vao);
-        gl::DeleteBuffers(1, &mut vbo);
-    }
 }
@@ -0,0 +1,95 @@
+extern crate pretty_env_logger;
+extern crate scarlet;
+
+use scarlet::{
+    import_scene_with_options, Application, ApplicationOptions, ImportOptions, MemoryUsage,
+};
+use std::env::args;
+use std::fs;
+use std::time::Instant;
+
+fn print_usage(usage: &MemoryUsage) {
+    let categories = [
+        ("textures", usage.textures),
+        ("buffers", usage.buffers),
+        ("vertex arrays", usage.vertex_arrays),
+        ("framebuffers", usage.framebuffers),
+    ];
+    for (name, category) in categories.iter() {
+        println!(
+            "  {:<14} {:>5} objects {:>12} bytes",
+            name, category.count, category.bytes
+        );
+    }
+    println!("  {:<14} {:>26} bytes", "total", usage.total_bytes());
+}
+
+fn bench(
+    app: &Application,
+    asset: &[u8],
+    options: &ImportOptions,
+    iterations: usize,
+) -> (Vec<f64>, MemoryUsage) {
+    let (width, height) = app.size();
+    let mut times = Vec::with_capacity(iterations);
+    let mut usage = MemoryUsage::default();
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let model = import_scene_with_options(app.gl(), asset, width, height, options);
+        times.push(start.elapsed().as_secs_f64() * 1000.0);
+        usage = app.gl().memory_usage();
+        drop(model);
+    }
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    (times, usage)
+}
+
+fn print_times(times: &[f64]) {
+    println!(
+        "  import time    min {:.2} ms, median {:.2} ms, max {:.2} ms",
+        times[0],
+        times[times.len() / 2],
+        times[times.len() - 1]
+    );
+}
+
+fn main() {
+    pretty_env_logger::init();
+    let mname = args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("scene/milch.glb"));
+    let iterations = args().nth(2).map_or(10, |s| {
+        s.parse::<usize>()
+            .ok()
+            .filter(|&n| n > 0)
+            .expect("iteration count must be a positive integer")
+    });
+    let mut opt = ApplicationOptions::default();
+    opt.title = String::from("Scarlet import benchmark");
+    let app = Application::with_options(&opt);
+    let asset = fs::read(&mname).expect("cannot read file");
+    let indexed = bench(&app, &asset, &ImportOptions::default(), iterations);
+    let deindexed = bench(
+        &app,
+        &asset,
+        &ImportOptions {
+            deindex: true,
+            ..ImportOptions::default()
+        },
+        iterations,
+    );
+    println!("{}: {} imports per run", mname, iterations);
+    for (name, (times, usage)) in [("indexed", &indexed), ("de-indexed", &deindexed)].iter() {
+        println!("{}:", name);
+        print_times(times);
+        println!("GPU memory after import:");
+        print_usage(usage);
+    }
+    let median = |times: &[f64]| times[times.len() / 2];
+    println!(
+        "indexing saves {} bytes of GPU memory ({:.1}%), indexed median import time is {:.2}x the de-indexed one",
+        deindexed.1.total_bytes() as isize - indexed.1.total_bytes() as isize,
+        100.0 * (1.0 - indexed.1.total_bytes() as f64 / deindexed.1.total_bytes() as f64),
+        median(&indexed.0) / median(&deindexed.0)
+    );
+}
@@ -9,20 +9,23 @@ extern crate pretty_env_logger;
 use glad_gles2::gl;
 use glutin::event::{ElementState, Event, KeyboardInput, StartCause, VirtualKeyCode, WindowEvent};
 use scarlet::scene::Scene;
-use scarlet::{import_scene, Application, ApplicationAction, ApplicationOptions};
+use scarlet::{
+    import_scene_from_path, Application, ApplicationAction, ApplicationOptions, PlaybackState,
+};
 use std::env::args;
 use std::f32;
-use std::fs;
 use std::time::Instant;
 
 fn main() {
     pretty_env_logger::init();
     let mut opt = ApplicationOptions::default();
     opt.title = String::from("Scarlet model viewer");
+    opt.resizable = true;
     let app = Application::with_options(&opt);
     let (width, height) = app.size();
-    let model = import_scene(
-        &fs::read(args().nth(1).expect("missing args")).expect("cannot read file"),
+    let model = import_scene_from_path(
+        app.gl(),
+        args().nth(1).expect("missing args"),
         width,
         height,
     );
@@ -36,14 +39,14 @@ fn main() {
                 let floor = model.get_node("Plane.002").unwrap();
                 let mut floor = floor.borrow_mut();
                 let mesh = floor.mesh.as_mut().unwrap();
-                let material = &mut mesh.data[0].material;
+                let material = mesh.data[0].material.borrow();
                 material.roughness
             };
             let set_roughness = |model: &mut Scene, factor: f32| {
                 let floor = model.get_node("Plane.002").unwrap();
                 let mut floor = floor.borrow_mut();
                 let mesh = floor.mesh.as_mut().unwrap();
-                let mut material = &mut mesh.data[0].material;
+                let mut material = mesh.data[0].material.borrow_mut();
                 material.roughness = factor;
             };
             let frames = [
@@ -53,6 +56,8 @@ fn main() {
                 "albedo",
                 "metalness",
                 "roughness",
+                "occlusion",
+                "emissive",
                 "depth",
                 "pbr",
                 "ssr",
@@ -62,6 +67,20 @@ fn main() {
                     event: WindowEvent::CloseRequested,
                     ..
                 } => ApplicationAction::Quit,
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(size),
+                    ..
+                } => {
+                    model.resize(size.width, size.height);
+                    ApplicationAction::Nothing
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::ScaleFactorChanged { new_inner_size, .. },
+                    ..
+                } => {
+                    model.resize(new_inner_size.width, new_inner_size.height);
+                    ApplicationAction::Nothing
+                }
                 Event::RedrawRequested { .. }
                 | Event::NewEvents(StartCause::ResumeTimeReached { .. }) => unsafe {
                     model.draw(frames[*frame_index], true);
@@ -141,6 +160,69 @@ fn main() {
                     info!("Roughness: {}", r);
                     ApplicationAction::Nothing
                 }
+                Event::WindowEvent {
+                    event:
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::C),
+                                    ..
+                                },
+                            ..
+                        },
+                    ..
+                } => {
+                    let cameras = model.cameras();
+                    let next = (model.active_camera() + 1) % cameras.len();
+                    model.set_active_camera(next);
+                    info!("Camera: {}", cameras[next]);
+                    ApplicationAction::Nothing
+                }
+                Event::WindowEvent {
+                    event:
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::S),
+                                    ..
+                                },
+                            ..
+                        },
+                    ..
+                } => {
+                    let scenes = model.scenes();
+                    let next = (model.active_scene() + 1) % scenes.len();
+                    match model.set_active_scene(next) {
+                        Ok(()) => info!("Scene: {}", scenes[next]),
+                        Err(e) => error!("Scene {}: {}", scenes[next], e),
+                    }
+                    ApplicationAction::Nothing
+                }
+                Event::WindowEvent {
+                    event:
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::Space),
+                                    ..
+                                },
+                            ..
+                        },
+                    ..
+                } => {
+                    let player = model.animation_player_mut();
+                    for clip in 0..player.clips().len() {
+                        if player.state(clip) == PlaybackState::Playing {
+                            player.pause(clip);
+                        } else {
+                            player.play(clip);
+                        }
+                    }
+                    ApplicationAction::Nothing
+                }
                 _ => ApplicationAction::Nothing,
             }
         },
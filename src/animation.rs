@@ -0,0 +1,1499 @@
+use crate::scene::Transform;
+use gltf::animation::util::ReadOutputs;
+use na::geometry::{Quaternion, Translation3, UnitQuaternion};
+use nalgebra as na;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// How a sampler fills the time between two keyframes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    CubicSpline,
+}
+
+impl From<gltf::animation::Interpolation> for Interpolation {
+    fn from(interpolation: gltf::animation::Interpolation) -> Self {
+        match interpolation {
+            gltf::animation::Interpolation::Step => Interpolation::Step,
+            gltf::animation::Interpolation::Linear => Interpolation::Linear,
+            gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+        }
+    }
+}
+
+/// The node property a channel animates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Property {
+    Translation,
+    Rotation,
+    Scale,
+    Weights,
+}
+
+/// Keyframes of a single property. Values are stored flat, `components`
+/// floats per key, and cubic-spline samplers keep the in-tangent, value and
+/// out-tangent of every key in that order.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    interpolation: Interpolation,
+    times: Vec<f32>,
+    values: Vec<f32>,
+    components: usize,
+}
+
+impl Sampler {
+    /// Returns `None` when the number of values doesn't match the keyframes.
+    pub fn new(interpolation: Interpolation, times: Vec<f32>, values: Vec<f32>) -> Option<Self> {
+        let elements = match interpolation {
+            Interpolation::CubicSpline => times.len() * 3,
+            _ => times.len(),
+        };
+        if elements == 0 || values.is_empty() || !values.len().is_multiple_of(elements) {
+            return None;
+        }
+        Some(Sampler {
+            interpolation,
+            components: values.len() / elements,
+            times,
+            values,
+        })
+    }
+
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    pub fn times(&self) -> &[f32] {
+        &self.times
+    }
+
+    /// Floats per value, which for weights is the number of morph targets.
+    pub fn components(&self) -> usize {
+        self.components
+    }
+
+    pub fn start(&self) -> f32 {
+        self.times[0]
+    }
+
+    pub fn end(&self) -> f32 {
+        *self.times.last().unwrap()
+    }
+
+    fn value(&self, key: usize) -> &[f32] {
+        let c = self.components;
+        match self.interpolation {
+            Interpolation::CubicSpline => &self.values[(key * 3 + 1) * c..(key * 3 + 2) * c],
+            _ => &self.values[key * c..(key + 1) * c],
+        }
+    }
+
+    fn in_tangent(&self, key: usize) -> &[f32] {
+        let c = self.components;
+        &self.values[key * 3 * c..(key * 3 + 1) * c]
+    }
+
+    fn out_tangent(&self, key: usize) -> &[f32] {
+        let c = self.components;
+        &self.values[(key * 3 + 2) * c..(key * 3 + 3) * c]
+    }
+
+    /// The key at or before `time` and how far `time` is towards the next
+    /// one, `None` on a key or past either end of the track.
+    fn segment(&self, time: f32) -> (usize, Option<f32>) {
+        let next = self
+            .times
+            .iter()
+            .position(|&t| t > time)
+            .unwrap_or(self.times.len());
+        if next == 0 {
+            return (0, None);
+        }
+        if next == self.times.len() || self.times[next - 1] == time {
+            return (next - 1, None);
+        }
+        let (t0, t1) = (self.times[next - 1], self.times[next]);
+        (next - 1, Some((time - t0) / (t1 - t0)))
+    }
+
+    /// Component-wise value at `time`, clamped to the first and last keys.
+    pub fn sample(&self, time: f32) -> Vec<f32> {
+        let (key, alpha) = match self.segment(time) {
+            (key, Some(alpha)) => (key, alpha),
+            (key, None) => return self.value(key).to_vec(),
+        };
+        match self.interpolation {
+            Interpolation::Step => self.value(key).to_vec(),
+            Interpolation::Linear => self
+                .value(key)
+                .iter()
+                .zip(self.value(key + 1))
+                .map(|(a, b)| a * (1.0 - alpha) + b * alpha)
+                .collect(),
+            Interpolation::CubicSpline => {
+                // Hermite spline, the tangents are scaled by the key interval
+                let dt = self.times[key + 1] - self.times[key];
+                let (s, s2, s3) = (alpha, alpha * alpha, alpha * alpha * alpha);
+                let h00 = 2.0 * s3 - 3.0 * s2 + 1.0;
+                let h10 = (s3 - 2.0 * s2 + s) * dt;
+                let h01 = -2.0 * s3 + 3.0 * s2;
+                let h11 = (s3 - s2) * dt;
+                (0..self.components)
+                    .map(|i| {
+                        h00 * self.value(key)[i]
+                            + h10 * self.out_tangent(key)[i]
+                            + h01 * self.value(key + 1)[i]
+                            + h11 * self.in_tangent(key + 1)[i]
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Rotation at `time`. Linear samplers slerp between the keys, splines
+    /// are evaluated per component and normalized.
+    pub fn sample_rotation(&self, time: f32) -> UnitQuaternion<f32> {
+        let quaternion =
+            |v: &[f32]| UnitQuaternion::from_quaternion(Quaternion::new(v[3], v[0], v[1], v[2]));
+        match (self.interpolation, self.segment(time)) {
+            (Interpolation::Linear, (key, Some(alpha))) => slerp(
+                &quaternion(self.value(key)),
+                &quaternion(self.value(key + 1)),
+                alpha,
+            ),
+            _ => quaternion(&self.sample(time)),
+        }
+    }
+}
+
+/// Animated properties of a node, the ones no channel targets are left
+/// `None`.
+#[derive(Debug, Clone, Default)]
+pub struct Pose {
+    pub transform: Transform,
+    pub weights: Option<Vec<f32>>,
+}
+
+impl Pose {
+    /// The motion from `reference` to this pose, as translation and weight
+    /// offsets, a rotation applied after the reference one and scale
+    /// factors.
+    fn relative_to(&self, reference: &Pose) -> Pose {
+        let (t, r) = (&self.transform, &reference.transform);
+        Pose {
+            transform: Transform {
+                translation: t
+                    .translation
+                    .zip(r.translation)
+                    .map(|(t, r)| Translation3::from(t.vector - r.vector)),
+                rotation: t.rotation.zip(r.rotation).map(|(t, r)| r.inverse() * t),
+                scaling: t
+                    .scaling
+                    .zip(r.scaling)
+                    .map(|(t, r)| t.zip_map(&r, |t, r| if r == 0.0 { 1.0 } else { t / r })),
+            },
+            weights: self.weights.as_ref().zip(reference.weights.as_ref()).map(
+                |(weights, reference)| weights.iter().zip(reference).map(|(w, r)| w - r).collect(),
+            ),
+        }
+    }
+}
+
+/// Spherical interpolation the short way around.
+pub(crate) fn slerp(
+    a: &UnitQuaternion<f32>,
+    b: &UnitQuaternion<f32>,
+    alpha: f32,
+) -> UnitQuaternion<f32> {
+    let b = if a.coords.dot(&b.coords) < 0.0 {
+        UnitQuaternion::new_unchecked(-b.into_inner())
+    } else {
+        *b
+    };
+    a.try_slerp(&b, alpha, 1.0e-6).unwrap_or(*a)
+}
+
+fn lerp(a: &mut Vec<f32>, b: &[f32], alpha: f32) {
+    a.resize(a.len().max(b.len()), 0.0);
+    for (a, b) in a.iter_mut().zip(b) {
+        *a += (b - *a) * alpha;
+    }
+}
+
+/// A sampler bound to a property of a node.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub node: usize,
+    pub property: Property,
+    pub sampler: Sampler,
+}
+
+impl Channel {
+    /// Reads a glTF channel, `None` if its keyframes are missing or don't
+    /// match the property.
+    pub(crate) fn from_gltf(
+        channel: &gltf::animation::Channel,
+        buffers: &[gltf::buffer::Data],
+    ) -> Option<Self> {
+        let reader = channel.reader(|x| Some(&buffers[x.index()]));
+        let inputs: Vec<f32> = reader.read_inputs()?.collect();
+        let (property, components, values): (_, _, Vec<f32>) = match reader.read_outputs()? {
+            ReadOutputs::Translations(iter) => {
+                (Property::Translation, Some(3), iter.flatten().collect())
+            }
+            ReadOutputs::Rotations(iter) => (
+                Property::Rotation,
+                Some(4),
+                iter.into_f32().flatten().collect(),
+            ),
+            ReadOutputs::Scales(iter) => (Property::Scale, Some(3), iter.flatten().collect()),
+            ReadOutputs::MorphTargetWeights(iter) => {
+                (Property::Weights, None, iter.into_f32().collect())
+            }
+        };
+        let sampler = Sampler::new(channel.sampler().interpolation().into(), inputs, values)
+            .filter(|sampler| components.is_none_or(|c| sampler.components() == c))?;
+        Some(Channel {
+            node: channel.target().node().index(),
+            property,
+            sampler,
+        })
+    }
+
+    /// Writes the animated value at `time` into the pose of the node.
+    pub fn apply(&self, time: f32, pose: &mut Pose) {
+        let transform = &mut pose.transform;
+        match self.property {
+            Property::Translation => {
+                let v = self.sampler.sample(time);
+                transform.translation = Some(Translation3::new(v[0], v[1], v[2]));
+            }
+            Property::Rotation => {
+                transform.rotation = Some(self.sampler.sample_rotation(time));
+            }
+            Property::Scale => {
+                let v = self.sampler.sample(time);
+                transform.scaling = Some(na::Vector3::new(v[0], v[1], v[2]));
+            }
+            Property::Weights => {
+                pose.weights = Some(self.sampler.sample(time));
+            }
+        }
+    }
+}
+
+/// One of the animations of a glTF document.
+#[derive(Debug, Clone)]
+pub struct Animation {
+    name: String,
+    channels: Vec<Channel>,
+}
+
+impl Animation {
+    pub fn new(name: String, channels: Vec<Channel>) -> Self {
+        Animation { name, channels }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels
+    }
+
+    /// Time of the last keyframe of any channel.
+    pub fn duration(&self) -> f32 {
+        self.channels
+            .iter()
+            .map(|channel| channel.sampler.end())
+            .max_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+            .unwrap_or(0.0)
+    }
+}
+
+/// What a clip does once it reaches either end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaybackMode {
+    Loop,
+    /// Stops on the last pose and holds it.
+    Once,
+    /// Plays back and forth.
+    PingPong,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaybackState {
+    Playing,
+    /// Holds the pose at the current time.
+    Paused,
+    /// Doesn't pose the scene at all.
+    Stopped,
+}
+
+/// How a layer combines its clips with the pose of the layers below.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    /// Moves the pose towards the clips, averaged by their weights.
+    Override,
+    /// Adds the motion of every clip since its first keyframe.
+    Additive,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Layer {
+    mode: BlendMode,
+    weight: f32,
+}
+
+/// A change of clip weight over time.
+#[derive(Debug, Clone, Copy)]
+struct Fade {
+    from: f32,
+    to: f32,
+    duration: f32,
+    /// Player time the fade started at.
+    start: f64,
+    /// Weight given back once a fade out has stopped the clip.
+    restore: Option<f32>,
+}
+
+impl Fade {
+    /// Progress of the fade at player time `now`, from 0 to 1.
+    fn alpha(&self, now: f64) -> f32 {
+        if self.duration > 0.0 {
+            ((now - self.start) as f32 / self.duration).clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// A clip only changes when it is given a command, everything in between is
+/// worked out from the player time, so posing it at some time doesn't
+/// depend on the times posed before.
+#[derive(Debug)]
+struct Clip {
+    animation: Animation,
+    duration: f32,
+    // Ping-pong clips count up to twice the duration
+    time: f32,
+    /// Player time at which the clip was at `time`.
+    since: f64,
+    state: PlaybackState,
+    mode: PlaybackMode,
+    speed: f32,
+    weight: f32,
+    layer: usize,
+    fade: Option<Fade>,
+}
+
+impl Clip {
+    fn stop(&mut self) {
+        if let Some(restore) = self.fade.take().and_then(|fade| fade.restore) {
+            self.weight = restore;
+        }
+        self.state = PlaybackState::Stopped;
+        self.time = 0.0;
+    }
+
+    /// Time of the clip at player time `now` and whether a `Once` clip has
+    /// run off either end by then.
+    fn time_at(&self, now: f64) -> (f32, bool) {
+        if self.state != PlaybackState::Playing {
+            return (self.time, false);
+        }
+        let time = self.time + (now - self.since) as f32 * self.speed;
+        let period = match self.mode {
+            PlaybackMode::Loop => self.duration,
+            PlaybackMode::PingPong => 2.0 * self.duration,
+            PlaybackMode::Once => {
+                let finished = time < 0.0 || time > self.duration;
+                return (time.max(0.0).min(self.duration), finished);
+            }
+        };
+        // Landing right on the end keeps the last pose for that frame
+        if period > 0.0 && (time < 0.0 || time > period) {
+            (time.rem_euclid(period), false)
+        } else if period <= 0.0 {
+            (0.0, false)
+        } else {
+            (time, false)
+        }
+    }
+
+    /// Position in the animation at player time `now`.
+    fn position_at(&self, now: f64) -> f32 {
+        let (time, _) = self.time_at(now);
+        if self.mode == PlaybackMode::PingPong && time > self.duration {
+            2.0 * self.duration - time
+        } else {
+            time
+        }
+    }
+
+    /// Weight at player time `now`, and whether a fade out has stopped the
+    /// clip by then.
+    fn weight_at(&self, now: f64) -> (f32, bool) {
+        match self.fade {
+            Some(fade) if fade.alpha(now) >= 1.0 && fade.restore.is_some() => {
+                (fade.restore.unwrap(), true)
+            }
+            Some(fade) => (fade.from + (fade.to - fade.from) * fade.alpha(now), false),
+            None => (self.weight, false),
+        }
+    }
+
+    fn state_at(&self, now: f64) -> PlaybackState {
+        if self.state == PlaybackState::Stopped || self.weight_at(now).1 {
+            PlaybackState::Stopped
+        } else if self.time_at(now).1 {
+            PlaybackState::Paused
+        } else {
+            self.state
+        }
+    }
+
+    /// Brings the clip up to player time `now`, before a command changes it.
+    fn settle(&mut self, now: f64) {
+        if self.state == PlaybackState::Stopped {
+            self.since = now;
+            return;
+        }
+        let (time, finished) = self.time_at(now);
+        self.time = time;
+        self.since = now;
+        if finished {
+            self.state = PlaybackState::Paused;
+        }
+        if let Some(fade) = self.fade {
+            let (weight, stopped) = self.weight_at(now);
+            if stopped {
+                self.stop();
+            } else if fade.alpha(now) >= 1.0 {
+                self.weight = weight;
+                self.fade = None;
+            } else {
+                self.weight = weight;
+            }
+        }
+    }
+
+    /// Poses of the nodes the clip animates at `position`, relative to its
+    /// first keyframe for additive layers.
+    fn poses(&self, mode: BlendMode, position: f32) -> HashMap<usize, Pose> {
+        let mut poses: HashMap<usize, Pose> = HashMap::new();
+        let mut references: HashMap<usize, Pose> = HashMap::new();
+        for channel in self.animation.channels() {
+            channel.apply(position, poses.entry(channel.node).or_default());
+            if mode == BlendMode::Additive {
+                let reference = references.entry(channel.node).or_default();
+                channel.apply(channel.sampler.start(), reference);
+            }
+        }
+        if mode == BlendMode::Additive {
+            for (node, pose) in poses.iter_mut() {
+                *pose = pose.relative_to(&references[node]);
+            }
+        }
+        poses
+    }
+}
+
+/// Playback of the animations of a scene, each of them a clip that plays
+/// on its own clock. Clips are addressed by their index in `clips`, every
+/// method panics when given one out of range, and the same goes for layers.
+///
+/// Clips are mixed in layers, bottom to top. Layer 0 is the base override
+/// layer every clip starts in.
+///
+/// Commands take effect at the player time, which starts at zero and is
+/// moved by `advance` and `set_time`.
+#[derive(Debug)]
+pub struct AnimationPlayer {
+    clips: Vec<Clip>,
+    layers: Vec<Layer>,
+    now: f64,
+}
+
+impl AnimationPlayer {
+    /// Clips start stopped, looping at normal speed and full weight.
+    pub fn new(animations: Vec<Animation>) -> Self {
+        let clips = animations
+            .into_iter()
+            .map(|animation| Clip {
+                duration: animation.duration(),
+                animation,
+                time: 0.0,
+                since: 0.0,
+                state: PlaybackState::Stopped,
+                mode: PlaybackMode::Loop,
+                speed: 1.0,
+                weight: 1.0,
+                layer: 0,
+                fade: None,
+            })
+            .collect();
+        AnimationPlayer {
+            clips,
+            layers: vec![Layer {
+                mode: BlendMode::Override,
+                weight: 1.0,
+            }],
+            now: 0.0,
+        }
+    }
+
+    /// Names of the clips.
+    pub fn clips(&self) -> Vec<String> {
+        self.clips
+            .iter()
+            .map(|clip| clip.animation.name().to_string())
+            .collect()
+    }
+
+    /// Index of the first clip with the given name.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.clips
+            .iter()
+            .position(|clip| clip.animation.name() == name)
+    }
+
+    pub fn animation(&self, clip: usize) -> &Animation {
+        &self.clips[clip].animation
+    }
+
+    pub fn duration(&self, clip: usize) -> f32 {
+        self.clips[clip].duration
+    }
+
+    pub fn state(&self, clip: usize) -> PlaybackState {
+        self.clips[clip].state_at(self.now)
+    }
+
+    /// Starts or resumes the clip, stopped clips and finished `Once` clips
+    /// start over.
+    pub fn play(&mut self, clip: usize) {
+        let clip = &mut self.clips[clip];
+        clip.settle(self.now);
+        let finished = clip.mode == PlaybackMode::Once
+            && if clip.speed < 0.0 {
+                clip.time <= 0.0
+            } else {
+                clip.time >= clip.duration
+            };
+        if clip.state == PlaybackState::Stopped || finished {
+            clip.time = if clip.speed < 0.0 { clip.duration } else { 0.0 };
+        }
+        clip.state = PlaybackState::Playing;
+    }
+
+    pub fn pause(&mut self, clip: usize) {
+        let clip = &mut self.clips[clip];
+        clip.settle(self.now);
+        clip.state = PlaybackState::Paused;
+    }
+
+    pub fn stop(&mut self, clip: usize) {
+        self.clips[clip].stop();
+    }
+
+    pub fn play_all(&mut self) {
+        for clip in 0..self.clips.len() {
+            self.play(clip);
+        }
+    }
+
+    pub fn stop_all(&mut self) {
+        for clip in 0..self.clips.len() {
+            self.stop(clip);
+        }
+    }
+
+    /// Position of the clip in seconds, from zero to its duration.
+    pub fn time(&self, clip: usize) -> f32 {
+        self.clips[clip].position_at(self.now)
+    }
+
+    /// Moves the clip to `time`, clamped to its duration, without changing
+    /// whether it plays.
+    pub fn seek(&mut self, clip: usize, time: f32) {
+        let clip = &mut self.clips[clip];
+        clip.settle(self.now);
+        clip.time = time.max(0.0).min(clip.duration);
+    }
+
+    pub fn mode(&self, clip: usize) -> PlaybackMode {
+        self.clips[clip].mode
+    }
+
+    pub fn set_mode(&mut self, clip: usize, mode: PlaybackMode) {
+        let position = self.time(clip);
+        let clip = &mut self.clips[clip];
+        clip.settle(self.now);
+        clip.mode = mode;
+        clip.time = position;
+    }
+
+    pub fn speed(&self, clip: usize) -> f32 {
+        self.clips[clip].speed
+    }
+
+    /// Playback rate, negative speeds play the clip backwards.
+    pub fn set_speed(&mut self, clip: usize, speed: f32) {
+        let clip = &mut self.clips[clip];
+        clip.settle(self.now);
+        clip.speed = speed;
+    }
+
+    pub fn weight(&self, clip: usize) -> f32 {
+        self.clips[clip].weight_at(self.now).0
+    }
+
+    /// How much the clip moves the nodes it animates away from their own
+    /// transform and morph weights.
+    pub fn set_weight(&mut self, clip: usize, weight: f32) {
+        let clip = &mut self.clips[clip];
+        clip.settle(self.now);
+        clip.weight = weight.clamp(0.0, 1.0);
+        clip.fade = None;
+    }
+
+    /// Changes the weight of the clip to `weight` over `duration` seconds.
+    pub fn fade(&mut self, clip: usize, weight: f32, duration: f32) {
+        self.fade_with(clip, weight.clamp(0.0, 1.0), duration, false);
+    }
+
+    /// Fades the clip out and stops it.
+    pub fn fade_out(&mut self, clip: usize, duration: f32) {
+        self.fade_with(clip, 0.0, duration, true);
+    }
+
+    /// Fades `from` out and stops it while `to` starts if needed and fades
+    /// in to full weight.
+    pub fn crossfade(&mut self, from: usize, to: usize, duration: f32) {
+        self.fade_out(from, duration);
+        if self.state(to) == PlaybackState::Stopped {
+            self.clips[to].weight = 0.0;
+        }
+        self.play(to);
+        self.fade(to, 1.0, duration);
+    }
+
+    fn fade_with(&mut self, clip: usize, weight: f32, duration: f32, stop: bool) {
+        let now = self.now;
+        let clip = &mut self.clips[clip];
+        clip.settle(now);
+        let restore = match clip.fade {
+            Some(Fade {
+                restore: Some(restore),
+                ..
+            }) => restore,
+            _ => clip.weight,
+        };
+        clip.fade = Some(Fade {
+            from: clip.weight,
+            to: weight,
+            duration,
+            start: now,
+            restore: if stop { Some(restore) } else { None },
+        });
+        clip.settle(now);
+    }
+
+    /// Adds a layer on top of the others, at full weight.
+    pub fn add_layer(&mut self, mode: BlendMode) -> usize {
+        self.layers.push(Layer { mode, weight: 1.0 });
+        self.layers.len() - 1
+    }
+
+    /// Number of layers, including the base one.
+    pub fn layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn layer_mode(&self, layer: usize) -> BlendMode {
+        self.layers[layer].mode
+    }
+
+    pub fn layer_weight(&self, layer: usize) -> f32 {
+        self.layers[layer].weight
+    }
+
+    /// Scales the influence of every clip in the layer.
+    pub fn set_layer_weight(&mut self, layer: usize, weight: f32) {
+        self.layers[layer].weight = weight.clamp(0.0, 1.0);
+    }
+
+    /// Layer the clip is mixed in.
+    pub fn layer(&self, clip: usize) -> usize {
+        self.clips[clip].layer
+    }
+
+    pub fn set_layer(&mut self, clip: usize, layer: usize) {
+        assert!(layer < self.layers.len(), "no layer {}", layer);
+        self.clips[clip].layer = layer;
+    }
+
+    /// Moves the player time forward by `delta` seconds.
+    pub fn advance(&mut self, delta: f32) {
+        self.now += f64::from(delta);
+    }
+
+    /// Moves the player time to `time`, the moment later commands take
+    /// effect at.
+    pub fn set_time(&mut self, time: f64) {
+        self.now = time;
+    }
+
+    /// Poses of the playing and paused clips at the player time, layer by
+    /// layer.
+    pub fn evaluate(&self) -> Blend {
+        self.evaluate_at(self.now)
+    }
+
+    /// Poses of the playing and paused clips at player time `time`. They
+    /// only depend on the commands given, not on the times evaluated before.
+    pub fn evaluate_at(&self, time: f64) -> Blend {
+        let mut blend = Blend::default();
+        for (index, layer) in self.layers.iter().enumerate() {
+            if layer.weight <= 0.0 {
+                continue;
+            }
+            let mut layer_poses: HashMap<usize, LayerPose> = HashMap::new();
+            let clips = self.clips.iter().filter(|clip| {
+                clip.layer == index && clip.state_at(time) != PlaybackState::Stopped
+            });
+            for clip in clips {
+                let (weight, _) = clip.weight_at(time);
+                if weight <= 0.0 {
+                    continue;
+                }
+                for (node, pose) in clip.poses(layer.mode, clip.position_at(time)) {
+                    match layer.mode {
+                        BlendMode::Override => layer_poses
+                            .entry(node)
+                            .or_insert_with(|| LayerPose::new(layer.mode))
+                            .add(&pose, weight),
+                        // Additive clips stack, each at its own weight
+                        BlendMode::Additive => {
+                            let mut layer_pose = LayerPose::new(layer.mode);
+                            layer_pose.add(&pose, weight * layer.weight);
+                            blend.nodes.entry(node).or_default().push(layer_pose);
+                        }
+                    }
+                }
+            }
+            for (node, mut layer_pose) in layer_poses {
+                for weight in &mut layer_pose.weights {
+                    *weight = weight.min(1.0) * layer.weight;
+                }
+                blend.nodes.entry(node).or_default().push(layer_pose);
+            }
+        }
+        blend
+    }
+}
+
+/// What one layer does to a node. Override layers hold the weighted average
+/// of their clips, additive ones the motion of a single clip.
+#[derive(Debug, Clone)]
+struct LayerPose {
+    mode: BlendMode,
+    pose: Pose,
+    /// Total weight behind the translation, rotation, scale and morph
+    /// weights.
+    weights: [f32; 4],
+}
+
+impl LayerPose {
+    fn new(mode: BlendMode) -> Self {
+        LayerPose {
+            mode,
+            pose: Pose::default(),
+            weights: [0.0; 4],
+        }
+    }
+
+    /// Folds `pose` into the running average of the layer.
+    fn add(&mut self, pose: &Pose, weight: f32) {
+        for (slot, added) in properties(&pose.transform) {
+            self.weights[slot] += weight;
+            let current = &mut self.pose.transform;
+            *current = current.lerp(added, weight / self.weights[slot]);
+        }
+        if let Some(weights) = &pose.weights {
+            self.weights[3] += weight;
+            let alpha = weight / self.weights[3];
+            match &mut self.pose.weights {
+                Some(current) => lerp(current, weights, alpha),
+                None => self.pose.weights = Some(weights.clone()),
+            }
+        }
+    }
+
+    fn apply(&self, transform: &mut Transform, weights: &mut Vec<f32>) {
+        let ww = self.weights[3];
+        let rest = Transform::identity();
+        let base = transform.or(rest);
+        for (slot, pose) in properties(&self.pose.transform) {
+            let alpha = self.weights[slot];
+            match self.mode {
+                // A full weight replaces the property outright
+                BlendMode::Override => {
+                    let blended = if alpha >= 1.0 {
+                        pose
+                    } else {
+                        property(&base, slot).lerp(pose, alpha)
+                    };
+                    *transform = blended.or(*transform);
+                }
+                // The motion scaled down by the weight, on top of the property
+                BlendMode::Additive => {
+                    let motion = property(&rest, slot).lerp(pose, alpha);
+                    match slot {
+                        0 => {
+                            transform.translation = Some(Translation3::from(
+                                base.translation.unwrap().vector
+                                    + motion.translation.unwrap().vector,
+                            ))
+                        }
+                        1 => {
+                            transform.rotation =
+                                Some(base.rotation.unwrap() * motion.rotation.unwrap())
+                        }
+                        _ => {
+                            transform.scaling = Some(
+                                base.scaling
+                                    .unwrap()
+                                    .component_mul(&motion.scaling.unwrap()),
+                            )
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(w) = &self.pose.weights {
+            match self.mode {
+                BlendMode::Override if ww >= 1.0 => *weights = w.clone(),
+                BlendMode::Override => lerp(weights, w, ww),
+                BlendMode::Additive => {
+                    weights.resize(weights.len().max(w.len()), 0.0);
+                    for (current, delta) in weights.iter_mut().zip(w) {
+                        *current += delta * ww;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Translation, rotation or scale of `transform` alone, for slot 0, 1 or 2
+/// of `LayerPose::weights`.
+fn property(transform: &Transform, slot: usize) -> Transform {
+    let mut property = Transform::default();
+    match slot {
+        0 => property.translation = transform.translation,
+        1 => property.rotation = transform.rotation,
+        _ => property.scaling = transform.scaling,
+    }
+    property
+}
+
+/// The properties `transform` sets, one by one along with their slot.
+fn properties(transform: &Transform) -> Vec<(usize, Transform)> {
+    let set = [
+        transform.translation.is_some(),
+        transform.rotation.is_some(),
+        transform.scaling.is_some(),
+    ];
+    (0..3)
+        .filter(|&slot| set[slot])
+        .map(|slot| (slot, property(transform, slot)))
+        .collect()
+}
+
+/// Poses of every animated node for one frame.
+#[derive(Debug, Default)]
+pub struct Blend {
+    nodes: HashMap<usize, Vec<LayerPose>>,
+}
+
+impl Blend {
+    /// Whether no clip poses any node.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Layers the animation of `node` over its own transform and morph
+    /// weights.
+    pub fn apply(&self, node: usize, transform: &mut Transform, weights: &mut Vec<f32>) {
+        for layer_pose in self.nodes.get(&node).into_iter().flatten() {
+            layer_pose.apply(transform, weights);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1.0e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    // Rotation track of the AnimatedTriangle sample model
+    fn triangle() -> Sampler {
+        let h = 0.5f32.sqrt();
+        Sampler::new(
+            Interpolation::Linear,
+            vec![0.0, 0.25, 0.5, 0.75, 1.0],
+            vec![
+                0.0, 0.0, 0.0, 1.0, //
+                0.0, 0.0, h, h, //
+                0.0, 0.0, 1.0, 0.0, //
+                0.0, 0.0, h, -h, //
+                0.0, 0.0, 0.0, 1.0,
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn rejects_mismatched_outputs() {
+        assert!(Sampler::new(Interpolation::Linear, vec![0.0, 1.0], vec![0.0; 3]).is_none());
+        assert!(Sampler::new(Interpolation::CubicSpline, vec![0.0, 1.0], vec![0.0; 4]).is_none());
+        assert!(Sampler::new(Interpolation::Step, vec![], vec![]).is_none());
+        let sampler = Sampler::new(Interpolation::CubicSpline, vec![0.0, 1.0], vec![0.0; 18]);
+        assert_eq!(sampler.unwrap().components(), 3);
+    }
+
+    #[test]
+    fn step_holds_previous_key() {
+        // Translation jumping up, then down, then back
+        let sampler = Sampler::new(
+            Interpolation::Step,
+            vec![0.0, 0.5, 1.5, 2.0],
+            vec![
+                0.0, 0.0, 0.0, //
+                0.0, 1.0, 0.0, //
+                0.0, -1.0, 0.0, //
+                0.0, 0.0, 0.0,
+            ],
+        )
+        .unwrap();
+        assert_close(&sampler.sample(-1.0), &[0.0, 0.0, 0.0]);
+        assert_close(&sampler.sample(0.25), &[0.0, 0.0, 0.0]);
+        assert_close(&sampler.sample(0.5), &[0.0, 1.0, 0.0]);
+        assert_close(&sampler.sample(1.49), &[0.0, 1.0, 0.0]);
+        assert_close(&sampler.sample(1.5), &[0.0, -1.0, 0.0]);
+        assert_close(&sampler.sample(1.99), &[0.0, -1.0, 0.0]);
+        assert_close(&sampler.sample(3.0), &[0.0, 0.0, 0.0]);
+        // Rotations jump too instead of slerping
+        let h = 0.5f32.sqrt();
+        let sampler = Sampler::new(
+            Interpolation::Step,
+            vec![0.0, 1.0],
+            vec![0.0, 0.0, 0.0, 1.0, 0.0, h, 0.0, h],
+        )
+        .unwrap();
+        assert_close(
+            sampler.sample_rotation(0.99).coords.as_slice(),
+            &[0.0, 0.0, 0.0, 1.0],
+        );
+        assert_close(
+            sampler.sample_rotation(1.0).coords.as_slice(),
+            &[0.0, h, 0.0, h],
+        );
+    }
+
+    #[test]
+    fn linear_clamps_outside_keys() {
+        let sampler = Sampler::new(
+            Interpolation::Linear,
+            vec![1.0, 2.0],
+            vec![0.0, 10.0, 20.0, 30.0],
+        )
+        .unwrap();
+        assert_close(&sampler.sample(0.0), &[0.0, 10.0]);
+        assert_close(&sampler.sample(1.25), &[5.0, 15.0]);
+        assert_close(&sampler.sample(3.0), &[20.0, 30.0]);
+    }
+
+    #[test]
+    fn linear_rotation_slerps() {
+        let sampler = triangle();
+        // A quarter turn about z every quarter second
+        let q = sampler.sample_rotation(0.125);
+        assert_close(q.coords.as_slice(), &[0.0, 0.0, 0.38268343, 0.9238795]);
+        let q = sampler.sample_rotation(0.625);
+        let expected = UnitQuaternion::from_axis_angle(
+            &na::Vector3::z_axis(),
+            5.0 * std::f32::consts::FRAC_PI_4,
+        );
+        assert!(q.angle_to(&expected) < 1.0e-3);
+        assert_close(
+            sampler.sample_rotation(1.0).coords.as_slice(),
+            &[0.0, 0.0, 0.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn cubic_spline_hits_keys_and_tangents() {
+        // Keys 2 and 0.5 seconds apart, in-tangent, value and out-tangent per
+        // key. The expected values are worked out by hand from the Hermite
+        // basis with the tangents scaled by the key interval.
+        let sampler = Sampler::new(
+            Interpolation::CubicSpline,
+            vec![1.0, 3.0, 3.5],
+            vec![
+                0.0, 0.0, 1.0, //
+                -2.0, 2.0, 4.0, //
+                0.0, 1.0, 0.0,
+            ],
+        )
+        .unwrap();
+        assert_close(&sampler.sample(0.0), &[0.0]);
+        assert_close(&sampler.sample(1.0), &[0.0]);
+        assert_close(&sampler.sample(1.5), &[0.78125]);
+        assert_close(&sampler.sample(2.0), &[1.75]);
+        assert_close(&sampler.sample(3.0), &[2.0]);
+        assert_close(&sampler.sample(3.25), &[1.75]);
+        assert_close(&sampler.sample(3.5), &[1.0]);
+        assert_close(&sampler.sample(4.0), &[1.0]);
+    }
+
+    #[test]
+    fn weights_keep_targets_together() {
+        // Two morph targets, all the weights of a key side by side
+        let sampler = Sampler::new(
+            Interpolation::Linear,
+            vec![0.0, 1.0, 2.0],
+            vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+        )
+        .unwrap();
+        assert_eq!(sampler.components(), 2);
+        assert_close(&sampler.sample(0.5), &[0.5, 0.0]);
+        assert_close(&sampler.sample(1.5), &[0.5, 0.5]);
+        // Splines hold the in-tangents of every target, then the values, then
+        // the out-tangents
+        let sampler = Sampler::new(
+            Interpolation::CubicSpline,
+            vec![0.0, 1.0],
+            vec![
+                0.0, 0.0, 0.0, 1.0, 2.0, 0.0, //
+                0.0, -1.0, 1.0, 0.0, 0.0, 0.0,
+            ],
+        )
+        .unwrap();
+        assert_eq!(sampler.components(), 2);
+        assert_close(&sampler.sample(0.0), &[0.0, 1.0]);
+        assert_close(&sampler.sample(0.5), &[0.75, 0.625]);
+        assert_close(&sampler.sample(1.0), &[1.0, 0.0]);
+    }
+
+    #[test]
+    fn cubic_spline_with_zero_tangents_eases() {
+        let sampler = Sampler::new(
+            Interpolation::CubicSpline,
+            vec![0.0, 1.0],
+            vec![0.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+        )
+        .unwrap();
+        // smoothstep
+        assert_close(&sampler.sample(0.5), &[0.5]);
+        assert_close(&sampler.sample(0.25), &[0.15625]);
+    }
+
+    #[test]
+    fn cubic_spline_rotation_is_normalized() {
+        let h = 0.5f32.sqrt();
+        let sampler = Sampler::new(
+            Interpolation::CubicSpline,
+            vec![0.0, 1.0],
+            vec![
+                0.0, 0.0, 0.0, 0.0, //
+                0.0, 0.0, 0.0, 1.0, //
+                0.0, 0.0, 0.0, 0.0, //
+                0.0, 0.0, 0.0, 0.0, //
+                0.0, 0.0, h, h, //
+                0.0, 0.0, 0.0, 0.0,
+            ],
+        )
+        .unwrap();
+        let q = sampler.sample_rotation(0.5);
+        assert!((q.coords.norm() - 1.0).abs() < 1.0e-6);
+        assert!((q.angle() - std::f32::consts::FRAC_PI_4).abs() < 1.0e-2);
+    }
+
+    #[test]
+    fn channels_write_pose() {
+        let channel = Channel {
+            node: 0,
+            property: Property::Weights,
+            sampler: Sampler::new(
+                Interpolation::Linear,
+                vec![0.0, 1.0],
+                vec![0.0, 1.0, 1.0, 0.0],
+            )
+            .unwrap(),
+        };
+        let mut pose = Pose::default();
+        channel.apply(0.25, &mut pose);
+        assert_close(pose.weights.as_ref().unwrap(), &[0.25, 0.75]);
+        assert!(pose.transform.rotation.is_none());
+        let animation = Animation::new(String::from("Triangle"), vec![channel]);
+        assert_eq!(animation.duration(), 1.0);
+    }
+
+    fn player() -> AnimationPlayer {
+        let channel = Channel {
+            node: 3,
+            property: Property::Translation,
+            sampler: Sampler::new(
+                Interpolation::Linear,
+                vec![0.0, 2.0],
+                vec![0.0, 0.0, 0.0, 4.0, 0.0, 0.0],
+            )
+            .unwrap(),
+        };
+        AnimationPlayer::new(vec![Animation::new(String::from("Door"), vec![channel])])
+    }
+
+    fn pose(player: &AnimationPlayer, node: usize) -> Transform {
+        let mut transform = Transform::identity();
+        player
+            .evaluate()
+            .apply(node, &mut transform, &mut Vec::new());
+        transform
+    }
+
+    fn position(player: &AnimationPlayer) -> f32 {
+        pose(player, 3).translation.unwrap().vector.x
+    }
+
+    #[test]
+    fn stopped_clips_do_not_pose() {
+        let mut player = player();
+        assert_eq!(player.find("Door"), Some(0));
+        assert_eq!(player.find("Window"), None);
+        player.advance(1.0);
+        assert!(player.evaluate().is_empty());
+        player.play(0);
+        player.advance(0.5);
+        assert_close(&[position(&player)], &[1.0]);
+        player.pause(0);
+        player.advance(0.5);
+        assert_close(&[position(&player)], &[1.0]);
+        player.stop(0);
+        assert!(player.evaluate().is_empty());
+        assert_eq!(player.time(0), 0.0);
+    }
+
+    #[test]
+    fn loop_wraps_around() {
+        let mut player = player();
+        player.play(0);
+        player.advance(2.0);
+        assert_eq!(player.time(0), 2.0);
+        player.advance(0.5);
+        assert_close(&[player.time(0)], &[0.5]);
+        player.set_speed(0, -1.0);
+        player.advance(1.0);
+        assert_close(&[player.time(0)], &[1.5]);
+    }
+
+    #[test]
+    fn once_holds_last_pose() {
+        let mut player = player();
+        player.set_mode(0, PlaybackMode::Once);
+        player.play(0);
+        player.advance(3.0);
+        assert_eq!(player.state(0), PlaybackState::Paused);
+        assert_close(&[position(&player)], &[4.0]);
+        player.play(0);
+        assert_eq!(player.time(0), 0.0);
+        player.set_speed(0, 2.0);
+        player.advance(0.5);
+        assert_close(&[position(&player)], &[2.0]);
+    }
+
+    #[test]
+    fn ping_pong_reverses() {
+        let mut player = player();
+        player.set_mode(0, PlaybackMode::PingPong);
+        player.play(0);
+        player.advance(1.5);
+        assert_close(&[player.time(0)], &[1.5]);
+        player.advance(1.0);
+        assert_close(&[player.time(0)], &[1.5]);
+        player.advance(2.0);
+        assert_close(&[player.time(0)], &[0.5]);
+        player.seek(0, 1.0);
+        assert_close(&[position(&player)], &[2.0]);
+    }
+
+    #[test]
+    fn weight_blends_from_rest() {
+        let mut player = player();
+        player.play(0);
+        player.seek(0, 2.0);
+        player.set_weight(0, 0.25);
+        let transform = pose(&player, 3);
+        assert_close(
+            transform.translation.unwrap().vector.as_slice(),
+            &[1.0, 0.0, 0.0],
+        );
+        assert_close(transform.scaling.unwrap().as_slice(), &[1.0, 1.0, 1.0]);
+    }
+
+    // Two clips moving node 3, one of them also turning it, and a morph
+    // weight clip on node 4
+    fn layered() -> AnimationPlayer {
+        let sampler =
+            |values: Vec<f32>| Sampler::new(Interpolation::Linear, vec![0.0, 1.0], values).unwrap();
+        let slide = Animation::new(
+            String::from("Slide"),
+            vec![Channel {
+                node: 3,
+                property: Property::Translation,
+                sampler: sampler(vec![0.0, 0.0, 0.0, 2.0, 0.0, 0.0]),
+            }],
+        );
+        let h = 0.5f32.sqrt();
+        let lift = Animation::new(
+            String::from("Lift"),
+            vec![
+                Channel {
+                    node: 3,
+                    property: Property::Translation,
+                    sampler: sampler(vec![0.0, 1.0, 0.0, 0.0, 3.0, 0.0]),
+                },
+                Channel {
+                    node: 3,
+                    property: Property::Rotation,
+                    sampler: sampler(vec![0.0, 0.0, 0.0, 1.0, 0.0, h, 0.0, h]),
+                },
+                Channel {
+                    node: 4,
+                    property: Property::Weights,
+                    sampler: sampler(vec![0.5, 0.5, 1.0, 0.0]),
+                },
+            ],
+        );
+        AnimationPlayer::new(vec![slide, lift])
+    }
+
+    #[test]
+    fn crossfade_averages_clips() {
+        let mut player = layered();
+        player.play(0);
+        player.seek(0, 1.0);
+        player.pause(0);
+        player.crossfade(0, 1, 2.0);
+        player.pause(1);
+        player.seek(1, 1.0);
+        player.advance(0.5);
+        assert_close(&[player.weight(0), player.weight(1)], &[0.75, 0.25]);
+        let transform = pose(&player, 3);
+        assert_close(
+            transform.translation.unwrap().vector.as_slice(),
+            &[1.5, 0.75, 0.0],
+        );
+        // Only the incoming clip turns the node, by its own weight
+        let angle = transform.rotation.unwrap().angle();
+        assert_close(&[angle], &[std::f32::consts::FRAC_PI_2 * 0.25]);
+        player.advance(1.5);
+        assert_eq!(player.state(0), PlaybackState::Stopped);
+        assert_eq!(player.weight(0), 1.0);
+        assert_eq!(player.weight(1), 1.0);
+        let transform = pose(&player, 3);
+        assert_close(
+            transform.translation.unwrap().vector.as_slice(),
+            &[0.0, 3.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn additive_layer_adds_motion() {
+        let mut player = layered();
+        let layer = player.add_layer(BlendMode::Additive);
+        assert_eq!(player.layers(), 2);
+        player.set_layer(1, layer);
+        player.play(0);
+        player.play(1);
+        player.seek(0, 1.0);
+        player.seek(1, 0.5);
+        let transform = pose(&player, 3);
+        assert_close(
+            transform.translation.unwrap().vector.as_slice(),
+            &[2.0, 1.0, 0.0],
+        );
+        let angle = transform.rotation.unwrap().angle();
+        assert_close(&[angle], &[std::f32::consts::FRAC_PI_4]);
+        player.set_layer_weight(layer, 0.5);
+        let mut weights = vec![1.0, 1.0];
+        player
+            .evaluate()
+            .apply(4, &mut Transform::identity(), &mut weights);
+        // Half of the change since the first key, (0.25, -0.25), at half weight
+        assert_close(&weights, &[1.125, 0.875]);
+    }
+
+    #[test]
+    fn evaluation_ignores_history() {
+        let commands = || {
+            let mut player = layered();
+            player.set_mode(0, PlaybackMode::Once);
+            player.set_mode(1, PlaybackMode::PingPong);
+            player.set_speed(1, 1.5);
+            player.play(0);
+            player.crossfade(0, 1, 2.0);
+            player
+        };
+        let pose_at = |player: &AnimationPlayer, time: f64| {
+            let mut transform = Transform::identity();
+            let mut weights = Vec::new();
+            let blend = player.evaluate_at(time);
+            blend.apply(3, &mut transform, &mut weights);
+            blend.apply(4, &mut transform, &mut weights);
+            let mut values = transform.translation.unwrap().vector.as_slice().to_vec();
+            values.extend_from_slice(transform.rotation.unwrap().coords.as_slice());
+            values.extend(weights);
+            values
+        };
+        let fresh = commands();
+        let mut replayed = commands();
+        // Jumping around, backwards included, as a looping keyframe clock does
+        for &time in &[5.0, 0.5, 3.0, 0.0, 7.25] {
+            replayed.set_time(time);
+            replayed.evaluate_at(time);
+        }
+        for &time in &[0.0, 0.25, 1.0, 1.75, 2.5, 4.0] {
+            assert_close(&pose_at(&replayed, time), &pose_at(&fresh, time));
+        }
+        // Past the crossfade only the ping-pong clip is left, two round trips
+        // and half a second in
+        replayed.set_time(3.0);
+        assert_eq!(replayed.state(0), PlaybackState::Stopped);
+        assert_close(&[replayed.time(1)], &[0.5]);
+    }
+
+    /// Document and channels of one of the glTF sample models, read from
+    /// the `2.0` directory of a glTF-Sample-Models checkout that
+    /// `SCARLET_SAMPLE_MODELS` points to. `None` when it isn't set.
+    fn sample_model(name: &str) -> Option<(gltf::Document, Vec<Channel>)> {
+        let dir = std::env::var_os("SCARLET_SAMPLE_MODELS")?;
+        let path = std::path::Path::new(&dir)
+            .join(name)
+            .join("glTF")
+            .join(format!("{}.gltf", name));
+        let (document, buffers, _) =
+            gltf::import(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        let channels = document
+            .animations()
+            .flat_map(|animation| animation.channels())
+            .map(|channel| Channel::from_gltf(&channel, &buffers).expect("invalid channel"))
+            .collect();
+        Some((document, channels))
+    }
+
+    #[test]
+    fn interpolation_test_step_and_cubic_spline() {
+        let (_, channels) = match sample_model("InterpolationTest") {
+            Some(model) => model,
+            None => return,
+        };
+        let mut step = Vec::new();
+        let mut cubic_spline = Vec::new();
+        for channel in &channels {
+            let sampler = &channel.sampler;
+            let (times, values, c) = (&sampler.times, &sampler.values, sampler.components);
+            match sampler.interpolation {
+                Interpolation::Step => {
+                    step.push(channel.property);
+                    for k in 0..times.len() - 1 {
+                        let key = &values[k * c..(k + 1) * c];
+                        let dt = times[k + 1] - times[k];
+                        assert_close(&sampler.sample(times[k]), key);
+                        assert_close(&sampler.sample(times[k] + 0.5 * dt), key);
+                        assert_close(&sampler.sample(times[k] + 0.99 * dt), key);
+                    }
+                }
+                Interpolation::CubicSpline => {
+                    cubic_spline.push(channel.property);
+                    // In-tangent, value and out-tangent of key `k`
+                    let element =
+                        |k: usize, i: usize| &values[(k * 3 + i) * c..(k * 3 + i + 1) * c];
+                    for k in 0..times.len() - 1 {
+                        let dt = times[k + 1] - times[k];
+                        for &s in &[0.25f32, 0.5, 0.75] {
+                            let (s2, s3) = (s * s, s * s * s);
+                            let expected: Vec<f32> = (0..c)
+                                .map(|i| {
+                                    (2.0 * s3 - 3.0 * s2 + 1.0) * element(k, 1)[i]
+                                        + (s3 - 2.0 * s2 + s) * dt * element(k, 2)[i]
+                                        + (-2.0 * s3 + 3.0 * s2) * element(k + 1, 1)[i]
+                                        + (s3 - s2) * dt * element(k + 1, 0)[i]
+                                })
+                                .collect();
+                            assert_close(&sampler.sample(times[k] + s * dt), &expected);
+                        }
+                        assert_close(&sampler.sample(times[k]), element(k, 1));
+                    }
+                }
+                Interpolation::Linear => {}
+            }
+        }
+        // One cube per interpolation and property
+        for properties in &[step, cubic_spline] {
+            for property in &[Property::Translation, Property::Rotation, Property::Scale] {
+                assert!(properties.contains(property), "no {:?} channel", property);
+            }
+        }
+    }
+
+    #[test]
+    fn animated_morph_cube_weights() {
+        let (document, channels) = match sample_model("AnimatedMorphCube") {
+            Some(model) => model,
+            None => return,
+        };
+        let targets = document
+            .meshes()
+            .flat_map(|mesh| mesh.primitives())
+            .map(|primitive| primitive.morph_targets().count())
+            .max()
+            .unwrap_or(0);
+        let weights: Vec<&Channel> = channels
+            .iter()
+            .filter(|channel| channel.property == Property::Weights)
+            .collect();
+        assert!(!weights.is_empty());
+        for channel in weights {
+            let sampler = &channel.sampler;
+            assert_eq!(sampler.components(), targets);
+            let (times, values, c) = (&sampler.times, &sampler.values, sampler.components);
+            let key = |k: usize| match sampler.interpolation {
+                Interpolation::CubicSpline => &values[(k * 3 + 1) * c..(k * 3 + 2) * c],
+                _ => &values[k * c..(k + 1) * c],
+            };
+            for k in 0..times.len() - 1 {
+                let (a, b) = (key(k), key(k + 1));
+                let mut pose = Pose::default();
+                channel.apply(times[k], &mut pose);
+                assert_close(pose.weights.as_ref().unwrap(), a);
+                if sampler.interpolation == Interpolation::Linear {
+                    let middle: Vec<f32> = a.iter().zip(b).map(|(a, b)| (a + b) / 2.0).collect();
+                    channel.apply((times[k] + times[k + 1]) / 2.0, &mut pose);
+                    assert_close(pose.weights.as_ref().unwrap(), &middle);
+                }
+            }
+        }
+    }
+}
@@ -1,16 +1,22 @@
-use glad_gles2::gl;
+use crate::context::GlContext;
+use crate::error::{Error, Result};
 use glutin::dpi::LogicalSize;
-use glutin::event::Event;
+use glutin::event::{Event, WindowEvent};
 use glutin::event_loop::{ControlFlow, EventLoop};
-use glutin::window::{Fullscreen, WindowBuilder};
+use glutin::window::{Fullscreen, Window, WindowBuilder};
 use glutin::{ContextBuilder, GlProfile, GlRequest, PossiblyCurrent, WindowedContext};
 use std::env::var;
-use std::ffi::CStr;
-use std::os::raw::c_char;
-use std::ptr::null;
+use std::process::exit;
 use std::thread::yield_now;
 use std::time::{Duration, Instant};
 
+#[cfg(target_os = "linux")]
+use crate::headless::HeadlessContext;
+#[cfg(target_os = "linux")]
+use glutin::event::StartCause;
+#[cfg(target_os = "linux")]
+use std::thread::sleep;
+
 /*#[cfg(target_os = "linux")]
 fn is_wayland(ev: &EventLoop<()>) -> bool {
     use glutin::platform::unix::EventLoopWindowTargetExtUnix;
@@ -22,74 +28,15 @@ fn is_wayland(_: &EventLoop) -> bool {
     false
 }*/
 
-thread_local! {
-    pub(crate) static GL: std::cell::Cell<bool> = std::cell::Cell::new(false);
-    pub(crate) static DEBUG_GL: std::cell::Cell<bool> = std::cell::Cell::new(false);
-}
-
-pub(crate) fn have_gl() -> bool {
-    GL.with(|f| f.get())
-}
-
-pub(crate) fn set_gl(value: bool) {
-    GL.with(|f| f.set(value))
-}
-
-pub(crate) fn have_debug_gl() -> bool {
-    DEBUG_GL.with(|f| f.get())
-}
-
-pub(crate) fn set_debug_gl(value: bool) {
-    DEBUG_GL.with(|f| f.set(value))
-}
-
-extern "system" fn gldebug(
-    source: gl::GLenum,
-    type_: gl::GLenum,
-    id: gl::GLuint,
-    severity: gl::GLenum,
-    _: gl::GLsizei,
-    message: *const gl::GLchar,
-    _: *mut gl::GLvoid,
-) {
-    let source = match source {
-        gl::GL_DEBUG_SOURCE_API_KHR => "API",
-        gl::GL_DEBUG_SOURCE_WINDOW_SYSTEM_KHR => "WINDOW_SYSTEM",
-        gl::GL_DEBUG_SOURCE_SHADER_COMPILER_KHR => "SHADER_COMPILER",
-        gl::GL_DEBUG_SOURCE_THIRD_PARTY_KHR => "THIRD_PARTY",
-        gl::GL_DEBUG_SOURCE_APPLICATION_KHR => "APPLICATION",
-        gl::GL_DEBUG_SOURCE_OTHER_KHR => "OTHER",
-        _ => "NULL",
-    };
-    let type_ = match type_ {
-        gl::GL_DEBUG_TYPE_ERROR_KHR => "ERROR",
-        gl::GL_DEBUG_TYPE_DEPRECATED_BEHAVIOR_KHR => "DEPRECATED_BEHAVIOR",
-        gl::GL_DEBUG_TYPE_UNDEFINED_BEHAVIOR_KHR => "UNDEFINED_BEHAVIOR",
-        gl::GL_DEBUG_TYPE_PORTABILITY_KHR => "PORTABILITY",
-        gl::GL_DEBUG_TYPE_PERFORMANCE_KHR => "PERFORMANCE",
-        gl::GL_DEBUG_TYPE_MARKER_KHR => "MARKER",
-        gl::GL_DEBUG_TYPE_PUSH_GROUP_KHR => "PUSH_GROUP",
-        gl::GL_DEBUG_TYPE_POP_GROUP_KHR => "POP_GROUP",
-        gl::GL_DEBUG_TYPE_OTHER_KHR => "OTHER",
-        _ => "NULL",
-    };
-    let message = unsafe { CStr::from_ptr(message) };
-    let msg = message.to_str().unwrap_or("NULL");
-    let msg = format!(
-        "{} | ID = {} | SOURCE = {} | TYPE = {}",
-        msg, id, source, type_
-    );
-    match severity {
-        gl::GL_DEBUG_SEVERITY_HIGH_KHR | gl::GL_DEBUG_SEVERITY_MEDIUM_KHR => error!("{}", msg),
-        gl::GL_DEBUG_SEVERITY_LOW_KHR => warn!("{}", msg),
-        gl::GL_DEBUG_SEVERITY_NOTIFICATION_KHR => info!("{}", msg),
-        _ => info!("{}", msg),
-    }
+enum Backend {
+    Windowed(EventLoop<()>, Box<WindowedContext<PossiblyCurrent>>),
+    #[cfg(target_os = "linux")]
+    Headless(HeadlessContext),
 }
 
 pub struct Application {
-    pub event_loop: EventLoop<()>,
-    pub context: WindowedContext<PossiblyCurrent>,
+    backend: Backend,
+    gl: GlContext,
     width: u32,
     height: u32,
     interval: Duration,
@@ -103,6 +50,8 @@ pub struct ApplicationOptions {
     pub height: u32,
     pub fps: f32,
     pub debug_gl: bool,
+    pub headless: bool,
+    pub resizable: bool,
 }
 
 pub enum ApplicationAction {
@@ -123,8 +72,61 @@ impl Application {
     }
 
     pub fn with_options(options: &ApplicationOptions) -> Application {
+        Self::try_with_options(options).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_with_options(options: &ApplicationOptions) -> Result<Application> {
+        let (backend, width, height) = if options.headless {
+            Self::create_headless(options)?
+        } else {
+            Self::create_windowed(options)?
+        };
+        let gl = match &backend {
+            Backend::Windowed(_, context) => unsafe {
+                GlContext::new(|s| context.get_proc_address(s), options.debug_gl)
+            },
+            #[cfg(target_os = "linux")]
+            Backend::Headless(context) => unsafe {
+                GlContext::new(|s| context.get_proc_address(s), options.debug_gl)
+            },
+        };
+        info!(
+            "{} size: {}×{}",
+            if options.headless {
+                "Framebuffer"
+            } else {
+                "Window"
+            },
+            width,
+            height
+        );
+        let interval = if options.fps > 0.0 {
+            Duration::from_micros((1000000.0 / options.fps) as u64)
+        } else {
+            Duration::from_millis(1)
+        };
+        info!(
+            "Max framerate: {} fps",
+            if options.fps > 0.0 {
+                format!("{}", options.fps)
+            } else {
+                String::from("∞")
+            }
+        );
+        Ok(Application {
+            backend,
+            gl,
+            width,
+            height,
+            interval,
+        })
+    }
+
+    fn create_windowed(options: &ApplicationOptions) -> Result<(Backend, u32, u32)> {
         let event_loop = EventLoop::new();
-        let primary_monitor = event_loop.primary_monitor().expect("No monitor found");
+        let primary_monitor = event_loop
+            .primary_monitor()
+            .ok_or_else(|| Error::Context(String::from("no monitor found")))?;
         let window_builder = WindowBuilder::new();
         let window_builder = window_builder.with_title(&options.title);
         let window_builder = if options.fullscreen {
@@ -135,7 +137,7 @@ impl Application {
         } else {
             window_builder.with_inner_size(LogicalSize::new(options.width, options.height))
         };
-        let window_builder = window_builder.with_resizable(false);
+        let window_builder = window_builder.with_resizable(options.resizable);
         let context_builder = ContextBuilder::new();
         let context_builder = context_builder.with_vsync(options.vsync);
         let context_builder = context_builder.with_gl(GlRequest::GlThenGles {
@@ -145,105 +147,108 @@ impl Application {
         let context_builder = context_builder.with_gl_profile(GlProfile::Core);
         let context = context_builder
             .build_windowed(window_builder, &event_loop)
-            .expect("Cannot create OpenGL context");
+            .map_err(|e| Error::Context(e.to_string()))?;
         let context = unsafe {
             context
                 .make_current()
-                .expect("Cannot make OpenGL context current")
+                .map_err(|(_, e)| Error::Context(format!("cannot make context current: {}", e)))?
         };
-        gl::load(|s| context.get_proc_address(s) as *const std::ffi::c_void);
-        set_gl(true);
-        if options.debug_gl {
-            if unsafe { gl::storage::DebugMessageCallbackKHR.is_loaded } {
-                set_debug_gl(true);
-            } else {
-                warn!("Cannot enable GL debugging, GL_KHR_debug not found!");
-            }
-        }
-        unsafe {
-            if have_debug_gl() {
-                gl::Enable(gl::GL_DEBUG_OUTPUT_KHR);
-                gl::DebugMessageCallbackKHR(gldebug, null());
-            }
-            gl::Enable(gl::GL_DEPTH_TEST);
-            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
-            let opengl_version = gl::GetString(gl::GL_VERSION);
-            info!(
-                "OpenGL version: {}",
-                CStr::from_ptr(opengl_version as *const i8)
-                    .to_str()
-                    .unwrap()
-            );
-            let mut max_draw_buffers: gl::GLint = 0;
-            let mut max_color_attachments: gl::GLint = 0;
-            let mut num_extensions: gl::GLint = 0;
-            gl::GetIntegerv(gl::GL_MAX_DRAW_BUFFERS, &mut max_draw_buffers);
-            gl::GetIntegerv(gl::GL_MAX_COLOR_ATTACHMENTS, &mut max_color_attachments);
-            gl::GetIntegerv(gl::GL_NUM_EXTENSIONS, &mut num_extensions);
-            info!("GL_MAX_DRAW_BUFFERS: {}", max_draw_buffers);
-            info!("GL_MAX_COLOR_ATTACHMENTS: {}", max_color_attachments);
-            if max_draw_buffers < 8 || max_color_attachments < 8 {
-                error!("GL_MAX_DRAW_BUFFERS or GL_MAX_COLOR_ATTACHMENTS is less than 8, expect breakage");
-            }
-            let mut ok = false;
-            for i in 0..num_extensions {
-                let ext = CStr::from_ptr(
-                    gl::GetStringi(gl::GL_EXTENSIONS, i as gl::GLuint) as *const c_char
-                );
-                if ext.to_str().expect("cannot decode GL_EXTENSIONS") == "GL_ARB_ES3_compatibility"
-                {
-                    ok = true;
-                    break;
-                }
-            }
-            if !ok {
-                error!("GL_ARB_ES3_compatibility not found, expect breakage");
-            }
-        }
         /*let current_monitor = if is_wayland(&event_loop) {
             event_loop.primary_monitor()
         } else {
             context.window().current_monitor()
         };*/
         let physical_size = context.window().inner_size();
-        let (width, height) = (physical_size.width as u32, physical_size.height as u32);
-        info!("Window size: {}×{}", width, height);
-        let interval = if options.fps > 0.0 {
-            Duration::from_micros((1000000.0 / options.fps) as u64)
-        } else {
-            Duration::from_millis(1)
-        };
-        info!(
-            "Max framerate: {} fps",
-            if options.fps > 0.0 {
-                format!("{}", options.fps)
-            } else {
-                String::from("∞")
-            }
-        );
-        Application {
-            event_loop,
-            context,
+        let (width, height) = (physical_size.width, physical_size.height);
+        Ok((
+            Backend::Windowed(event_loop, Box::new(context)),
             width,
             height,
-            interval,
+        ))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn create_headless(options: &ApplicationOptions) -> Result<(Backend, u32, u32)> {
+        let context = HeadlessContext::new(options.width, options.height)?;
+        Ok((Backend::Headless(context), options.width, options.height))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn create_headless(_options: &ApplicationOptions) -> Result<(Backend, u32, u32)> {
+        Err(Error::Context(String::from(
+            "headless mode is only supported on Linux",
+        )))
+    }
+
+    pub fn window(&self) -> Option<&Window> {
+        match &self.backend {
+            Backend::Windowed(_, context) => Some(context.window()),
+            #[cfg(target_os = "linux")]
+            Backend::Headless(_) => None,
         }
     }
 
+    pub fn is_headless(&self) -> bool {
+        match self.backend {
+            Backend::Windowed(..) => false,
+            #[cfg(target_os = "linux")]
+            Backend::Headless(_) => true,
+        }
+    }
+
+    /// Current size of the window, which follows resizes and scale factor
+    /// changes, or of the headless framebuffer.
     pub fn size(&self) -> (u32, u32) {
-        (self.width, self.height)
+        match self.window() {
+            Some(window) => {
+                let size = window.inner_size();
+                (size.width, size.height)
+            }
+            None => (self.width, self.height),
+        }
     }
 
+    pub fn gl(&self) -> &GlContext {
+        &self.gl
+    }
+
+    /// Runs the frame loop and exits the process once `f` quits.
     pub fn run<U: 'static, T: 'static + Fn(&mut U, Event<()>) -> ApplicationAction>(
         self,
-        mut up: U,
+        up: U,
         f: T,
     ) -> ! {
-        let context = self.context;
         let interval = self.interval;
+        let gl = self.gl;
+        let (event_loop, context) = match self.backend {
+            Backend::Windowed(event_loop, context) => (event_loop, context),
+            #[cfg(target_os = "linux")]
+            Backend::Headless(context) => {
+                Self::headless_loop(context, gl, interval, up, f);
+                exit(0)
+            }
+        };
+        // The user state owns the GPU resources, it's dropped on quit while
+        // the context is still current
+        let mut up = Some(up);
         let mut next_refresh = Instant::now() + interval;
-        self.event_loop.run(move |ev, _wt, cf| {
-            let action = f(&mut up, ev);
+        event_loop.run(move |ev, _wt, cf| {
+            let state = match up.as_mut() {
+                Some(state) => state,
+                None => return,
+            };
+            match &ev {
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(size),
+                    ..
+                } => context.resize(*size),
+                Event::WindowEvent {
+                    event: WindowEvent::ScaleFactorChanged { new_inner_size, .. },
+                    ..
+                } => context.resize(**new_inner_size),
+                _ => {}
+            }
+            let action = f(state, ev);
             match action {
                 ApplicationAction::Refresh => {
                     context.swap_buffers().expect("Cannot swap buffers");
@@ -251,7 +256,8 @@ impl Application {
                     *cf = ControlFlow::WaitUntil(next_refresh);
                 }
                 ApplicationAction::Quit => {
-                    set_gl(false);
+                    up.take();
+                    gl.invalidate();
                     *cf = ControlFlow::Exit
                 }
                 ApplicationAction::Nothing => {
@@ -261,6 +267,62 @@ impl Application {
             }
         })
     }
+
+    /// Runs the frame loop of a headless application and returns once `f`
+    /// quits, dropping the user state while the context is still current.
+    pub fn run_headless<U, T: Fn(&mut U, Event<()>) -> ApplicationAction>(
+        self,
+        up: U,
+        f: T,
+    ) -> Result<()> {
+        match self.backend {
+            Backend::Windowed(..) => Err(Error::Context(String::from(
+                "run_headless needs a headless application",
+            ))),
+            #[cfg(target_os = "linux")]
+            Backend::Headless(context) => {
+                Self::headless_loop(context, self.gl, self.interval, up, f);
+                Ok(())
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn headless_loop<U, T: Fn(&mut U, Event<()>) -> ApplicationAction>(
+        context: HeadlessContext,
+        gl: GlContext,
+        interval: Duration,
+        mut up: U,
+        f: T,
+    ) {
+        let mut action = f(&mut up, Event::NewEvents(StartCause::Init));
+        let mut next_refresh = Instant::now();
+        loop {
+            match action {
+                ApplicationAction::Refresh => {
+                    context.swap_buffers();
+                    next_refresh = Instant::now() + interval;
+                }
+                ApplicationAction::Quit => {
+                    drop(up);
+                    gl.invalidate();
+                    return;
+                }
+                ApplicationAction::Nothing => {}
+            }
+            let start = Instant::now();
+            if start < next_refresh {
+                sleep(next_refresh - start);
+            }
+            action = f(
+                &mut up,
+                Event::NewEvents(StartCause::ResumeTimeReached {
+                    start,
+                    requested_resume: next_refresh,
+                }),
+            );
+        }
+    }
 }
 
 impl Default for ApplicationOptions {
@@ -269,10 +331,10 @@ impl Default for ApplicationOptions {
             title: String::from("Scarlet"),
             fullscreen: var("SCARLET_FULLSCREEN")
                 .ok()
-                .map_or(false, |s| s.parse::<usize>().unwrap_or(0) != 0),
+                .is_some_and(|s| s.parse::<usize>().unwrap_or(0) != 0),
             vsync: var("SCARLET_VSYNC")
                 .ok()
-                .map_or(false, |s| s.parse::<usize>().unwrap_or(0) != 0),
+                .is_some_and(|s| s.parse::<usize>().unwrap_or(0) != 0),
             width: 1280,
             height: 720,
             fps: var("SCARLET_FPS")
@@ -280,7 +342,13 @@ impl Default for ApplicationOptions {
                 .map_or(0.0, |s| s.parse::<f32>().unwrap_or(0.0)),
             debug_gl: var("SCARLET_DEBUG_GL")
                 .ok()
-                .map_or(false, |s| s.parse::<usize>().unwrap_or(0) != 0),
+                .is_some_and(|s| s.parse::<usize>().unwrap_or(0) != 0),
+            headless: var("SCARLET_HEADLESS")
+                .ok()
+                .is_some_and(|s| s.parse::<usize>().unwrap_or(0) != 0),
+            resizable: var("SCARLET_RESIZABLE")
+                .ok()
+                .is_some_and(|s| s.parse::<usize>().unwrap_or(0) != 0),
         }
     }
 }
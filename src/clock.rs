@@ -0,0 +1,124 @@
+use std::fmt::Debug;
+use std::time::Instant;
+
+/// Source of the time, in seconds, the frames of a scene are drawn at.
+pub trait Clock: Debug {
+    /// Time of the frame about to be drawn.
+    fn tick(&mut self) -> f64;
+}
+
+/// Wall clock time since the clock was created.
+#[derive(Debug, Clone, Copy)]
+pub struct RealtimeClock {
+    start: Instant,
+}
+
+impl RealtimeClock {
+    pub fn new() -> Self {
+        RealtimeClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for RealtimeClock {
+    fn default() -> Self {
+        RealtimeClock::new()
+    }
+}
+
+impl Clock for RealtimeClock {
+    fn tick(&mut self) -> f64 {
+        Instant::now().duration_since(self.start).as_secs_f64()
+    }
+}
+
+/// Moves a fixed step every frame whatever the time drawing takes, for
+/// exporting at a steady frame rate.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock {
+    step: f64,
+    frame: u64,
+}
+
+impl FixedClock {
+    pub fn new(step: f64) -> Self {
+        FixedClock { step, frame: 0 }
+    }
+
+    pub fn with_fps(fps: f64) -> Self {
+        FixedClock::new(1.0 / fps)
+    }
+
+    /// Number of frames ticked so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+}
+
+impl Clock for FixedClock {
+    fn tick(&mut self) -> f64 {
+        let time = self.frame as f64 * self.step;
+        self.frame += 1;
+        time
+    }
+}
+
+/// Steps through a list of times, one per frame, starting over after the
+/// last one.
+#[derive(Debug, Clone)]
+pub struct KeyframeClock {
+    times: Vec<f64>,
+    frame: usize,
+}
+
+impl KeyframeClock {
+    pub fn new(times: Vec<f64>) -> Self {
+        KeyframeClock { times, frame: 0 }
+    }
+
+    pub fn times(&self) -> &[f64] {
+        &self.times
+    }
+
+    /// Index of the time the next tick returns, zero again once every time
+    /// has been ticked.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+}
+
+impl Clock for KeyframeClock {
+    fn tick(&mut self) -> f64 {
+        let time = self.times.get(self.frame).copied().unwrap_or(0.0);
+        self.frame = (self.frame + 1) % self.times.len().max(1);
+        time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyframe_clock_starts_over() {
+        let mut clock = KeyframeClock::new(vec![0.5, 1.0, 2.0]);
+        assert_eq!(clock.tick(), 0.5);
+        assert_eq!(clock.tick(), 1.0);
+        assert_eq!(clock.frame(), 2);
+        assert_eq!(clock.tick(), 2.0);
+        assert_eq!(clock.frame(), 0);
+        assert_eq!(clock.tick(), 0.5);
+        let mut empty = KeyframeClock::new(Vec::new());
+        assert_eq!(empty.tick(), 0.0);
+        assert_eq!(empty.frame(), 0);
+    }
+
+    #[test]
+    fn fixed_clock_steps() {
+        let mut clock = FixedClock::with_fps(25.0);
+        assert_eq!(clock.tick(), 0.0);
+        assert_eq!(clock.tick(), 0.04);
+        assert_eq!(clock.frame(), 2);
+    }
+}
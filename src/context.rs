@@ -0,0 +1,160 @@
+use crate::resource::{Category, MemoryUsage};
+use glad_gles2::gl;
+use std::cell::Cell;
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+use std::ptr::null;
+use std::rc::Rc;
+
+#[derive(Debug)]
+struct GlContextInner {
+    alive: Cell<bool>,
+    debug: bool,
+    usage: Cell<MemoryUsage>,
+}
+
+/// Handle to the GL context scarlet renders with.
+///
+/// Every GPU resource keeps a clone of the handle and only deletes its GL
+/// objects while the context is alive. The handle is neither `Send` nor
+/// `Sync`, so resources cannot leave the thread the context is current on.
+#[derive(Debug, Clone)]
+pub struct GlContext {
+    inner: Rc<GlContextInner>,
+}
+
+extern "system" fn gldebug(
+    source: gl::GLenum,
+    type_: gl::GLenum,
+    id: gl::GLuint,
+    severity: gl::GLenum,
+    _: gl::GLsizei,
+    message: *const gl::GLchar,
+    _: *mut gl::GLvoid,
+) {
+    let source = match source {
+        gl::GL_DEBUG_SOURCE_API_KHR => "API",
+        gl::GL_DEBUG_SOURCE_WINDOW_SYSTEM_KHR => "WINDOW_SYSTEM",
+        gl::GL_DEBUG_SOURCE_SHADER_COMPILER_KHR => "SHADER_COMPILER",
+        gl::GL_DEBUG_SOURCE_THIRD_PARTY_KHR => "THIRD_PARTY",
+        gl::GL_DEBUG_SOURCE_APPLICATION_KHR => "APPLICATION",
+        gl::GL_DEBUG_SOURCE_OTHER_KHR => "OTHER",
+        _ => "NULL",
+    };
+    let type_ = match type_ {
+        gl::GL_DEBUG_TYPE_ERROR_KHR => "ERROR",
+        gl::GL_DEBUG_TYPE_DEPRECATED_BEHAVIOR_KHR => "DEPRECATED_BEHAVIOR",
+        gl::GL_DEBUG_TYPE_UNDEFINED_BEHAVIOR_KHR => "UNDEFINED_BEHAVIOR",
+        gl::GL_DEBUG_TYPE_PORTABILITY_KHR => "PORTABILITY",
+        gl::GL_DEBUG_TYPE_PERFORMANCE_KHR => "PERFORMANCE",
+        gl::GL_DEBUG_TYPE_MARKER_KHR => "MARKER",
+        gl::GL_DEBUG_TYPE_PUSH_GROUP_KHR => "PUSH_GROUP",
+        gl::GL_DEBUG_TYPE_POP_GROUP_KHR => "POP_GROUP",
+        gl::GL_DEBUG_TYPE_OTHER_KHR => "OTHER",
+        _ => "NULL",
+    };
+    let message = unsafe { CStr::from_ptr(message) };
+    let msg = message.to_str().unwrap_or("NULL");
+    let msg = format!(
+        "{} | ID = {} | SOURCE = {} | TYPE = {}",
+        msg, id, source, type_
+    );
+    match severity {
+        gl::GL_DEBUG_SEVERITY_HIGH_KHR | gl::GL_DEBUG_SEVERITY_MEDIUM_KHR => error!("{}", msg),
+        gl::GL_DEBUG_SEVERITY_LOW_KHR => warn!("{}", msg),
+        gl::GL_DEBUG_SEVERITY_NOTIFICATION_KHR => info!("{}", msg),
+        _ => info!("{}", msg),
+    }
+}
+
+impl GlContext {
+    /// Loads the GL function pointers through `loader` and prepares the
+    /// global GL state scarlet relies on.
+    ///
+    /// # Safety
+    ///
+    /// The context the loader belongs to must be current on the calling
+    /// thread and must stay current until [`GlContext::invalidate`] is called.
+    pub unsafe fn new<F: Fn(&str) -> *const c_void>(loader: F, debug_gl: bool) -> GlContext {
+        gl::load(loader);
+        let debug = if debug_gl && !gl::storage::DebugMessageCallbackKHR.is_loaded {
+            warn!("Cannot enable GL debugging, GL_KHR_debug not found!");
+            false
+        } else {
+            debug_gl
+        };
+        if debug {
+            gl::Enable(gl::GL_DEBUG_OUTPUT_KHR);
+            gl::DebugMessageCallbackKHR(gldebug, null());
+        }
+        gl::Enable(gl::GL_DEPTH_TEST);
+        gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+        let opengl_version = gl::GetString(gl::GL_VERSION);
+        info!(
+            "OpenGL version: {}",
+            CStr::from_ptr(opengl_version as *const i8)
+                .to_str()
+                .unwrap()
+        );
+        let mut max_draw_buffers: gl::GLint = 0;
+        let mut max_color_attachments: gl::GLint = 0;
+        let mut num_extensions: gl::GLint = 0;
+        gl::GetIntegerv(gl::GL_MAX_DRAW_BUFFERS, &mut max_draw_buffers);
+        gl::GetIntegerv(gl::GL_MAX_COLOR_ATTACHMENTS, &mut max_color_attachments);
+        gl::GetIntegerv(gl::GL_NUM_EXTENSIONS, &mut num_extensions);
+        info!("GL_MAX_DRAW_BUFFERS: {}", max_draw_buffers);
+        info!("GL_MAX_COLOR_ATTACHMENTS: {}", max_color_attachments);
+        if max_draw_buffers < 8 || max_color_attachments < 8 {
+            error!(
+                "GL_MAX_DRAW_BUFFERS or GL_MAX_COLOR_ATTACHMENTS is less than 8, expect breakage"
+            );
+        }
+        let mut ok = false;
+        for i in 0..num_extensions {
+            let ext =
+                CStr::from_ptr(gl::GetStringi(gl::GL_EXTENSIONS, i as gl::GLuint) as *const c_char);
+            if ext.to_str().expect("cannot decode GL_EXTENSIONS") == "GL_ARB_ES3_compatibility" {
+                ok = true;
+                break;
+            }
+        }
+        if !ok {
+            error!("GL_ARB_ES3_compatibility not found, expect breakage");
+        }
+        GlContext {
+            inner: Rc::new(GlContextInner {
+                alive: Cell::new(true),
+                debug,
+                usage: Cell::new(MemoryUsage::default()),
+            }),
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.inner.alive.get()
+    }
+
+    pub fn is_debug(&self) -> bool {
+        self.inner.debug && self.is_alive()
+    }
+
+    /// Number of live resources and bytes of GPU memory they hold, per
+    /// resource category.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.inner.usage.get()
+    }
+
+    pub(crate) fn account(&self, category: Category, count: isize, bytes: isize) {
+        let mut usage = self.inner.usage.get();
+        let entry = usage.category_mut(category);
+        entry.count = (entry.count as isize + count) as usize;
+        entry.bytes = (entry.bytes as isize + bytes) as usize;
+        self.inner.usage.set(usage);
+    }
+
+    /// Marks the context as gone: resources dropped afterwards no longer try
+    /// to delete their GL objects.
+    pub fn invalidate(&self) {
+        self.inner.alive.set(false);
+    }
+}
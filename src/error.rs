@@ -0,0 +1,96 @@
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum Error {
+    Gltf(gltf::Error),
+    NoScene,
+    UnknownScene {
+        scene: String,
+    },
+    NoCamera,
+    UnknownCamera {
+        camera: String,
+    },
+    MissingAttribute {
+        mesh: String,
+        attribute: &'static str,
+    },
+    MissingFile {
+        uri: String,
+        path: PathBuf,
+    },
+    InvalidUri {
+        uri: String,
+    },
+    InvalidAnimation {
+        animation: String,
+        node: String,
+    },
+    ShaderSource {
+        name: String,
+    },
+    ShaderCompile {
+        name: String,
+        log: String,
+    },
+    ShaderLink {
+        name: String,
+        log: String,
+    },
+    Context(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Gltf(e) => write!(f, "Cannot import asset: {}", e),
+            Error::NoScene => write!(f, "No scenes in asset"),
+            Error::UnknownScene { scene } => write!(f, "No scene \"{}\" in asset", scene),
+            Error::NoCamera => write!(f, "There must be a camera in the scene"),
+            Error::UnknownCamera { camera } => write!(f, "No camera \"{}\" in scene", camera),
+            Error::MissingAttribute { mesh, attribute } => {
+                write!(f, "Mesh \"{}\": missing {} attribute", mesh, attribute)
+            }
+            Error::MissingFile { uri, path } => write!(
+                f,
+                "Missing external file \"{}\" (looked for {})",
+                uri,
+                path.display()
+            ),
+            Error::InvalidUri { uri } => write!(f, "Cannot read URI \"{}\"", uri),
+            Error::InvalidAnimation { animation, node } => write!(
+                f,
+                "Animation \"{}\": invalid channel targeting node \"{}\"",
+                animation, node
+            ),
+            Error::ShaderSource { name } => {
+                write!(f, "Shader \"{}\": source contains a NUL byte", name)
+            }
+            Error::ShaderCompile { name, log } => {
+                write!(f, "Cannot compile shader \"{}\": {}", name, log)
+            }
+            Error::ShaderLink { name, log } => {
+                write!(f, "Cannot link shader \"{}\": {}", name, log)
+            }
+            Error::Context(msg) => write!(f, "Cannot create OpenGL context: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Gltf(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<gltf::Error> for Error {
+    fn from(e: gltf::Error) -> Self {
+        Error::Gltf(e)
+    }
+}
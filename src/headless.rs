@@ -0,0 +1,147 @@
+use crate::error::{Error, Result};
+use khronos_egl as egl;
+use std::ffi::c_void;
+use std::ptr::null_mut;
+
+const EGL_PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+pub(crate) struct HeadlessContext {
+    egl: egl::DynamicInstance<egl::EGL1_5>,
+    display: egl::Display,
+    surface: egl::Surface,
+    context: egl::Context,
+}
+
+impl HeadlessContext {
+    pub fn new(width: u32, height: u32) -> Result<HeadlessContext> {
+        let egl = unsafe {
+            egl::DynamicInstance::<egl::EGL1_5>::load_required()
+                .map_err(|e| Error::Context(format!("cannot load libEGL: {}", e)))?
+        };
+        let client_extensions = egl
+            .query_string(None, egl::EXTENSIONS)
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let display = if client_extensions
+            .split(' ')
+            .any(|e| e == "EGL_MESA_platform_surfaceless")
+        {
+            egl.get_platform_display(
+                EGL_PLATFORM_SURFACELESS_MESA,
+                null_mut(),
+                &[egl::ATTRIB_NONE],
+            )
+            .map_err(|e| Error::Context(format!("cannot open surfaceless EGL display: {}", e)))?
+        } else {
+            egl.get_display(egl::DEFAULT_DISPLAY)
+                .ok_or_else(|| Error::Context(String::from("cannot open default EGL display")))?
+        };
+        let (major, minor) = egl
+            .initialize(display)
+            .map_err(|e| Error::Context(format!("cannot initialize EGL display: {}", e)))?;
+        info!("EGL version: {}.{}", major, minor);
+        // Same preference as the windowed backend: OpenGL 3.3 core, then OpenGL ES 3.0
+        let apis = [
+            (
+                egl::OPENGL_API,
+                egl::OPENGL_BIT,
+                vec![
+                    egl::CONTEXT_MAJOR_VERSION,
+                    3,
+                    egl::CONTEXT_MINOR_VERSION,
+                    3,
+                    egl::CONTEXT_OPENGL_PROFILE_MASK,
+                    egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                    egl::NONE,
+                ],
+            ),
+            (
+                egl::OPENGL_ES_API,
+                egl::OPENGL_ES3_BIT,
+                vec![egl::CONTEXT_CLIENT_VERSION, 3, egl::NONE],
+            ),
+        ];
+        let found = apis
+            .iter()
+            .find_map(|(api, renderable, context_attributes)| {
+                egl.bind_api(*api).ok()?;
+                let config_attributes = [
+                    egl::SURFACE_TYPE,
+                    egl::PBUFFER_BIT,
+                    egl::RENDERABLE_TYPE,
+                    *renderable,
+                    egl::RED_SIZE,
+                    8,
+                    egl::GREEN_SIZE,
+                    8,
+                    egl::BLUE_SIZE,
+                    8,
+                    egl::DEPTH_SIZE,
+                    24,
+                    egl::NONE,
+                ];
+                let config = egl
+                    .choose_first_config(display, &config_attributes)
+                    .ok()??;
+                let context = egl
+                    .create_context(display, config, None, context_attributes)
+                    .ok()?;
+                let surface_attributes = [
+                    egl::WIDTH,
+                    width as egl::Int,
+                    egl::HEIGHT,
+                    height as egl::Int,
+                    egl::NONE,
+                ];
+                let surface = match egl.create_pbuffer_surface(display, config, &surface_attributes)
+                {
+                    Ok(surface) => surface,
+                    Err(_) => {
+                        let _ = egl.destroy_context(display, context);
+                        return None;
+                    }
+                };
+                Some((surface, context))
+            });
+        let (surface, context) = match found {
+            Some(found) => found,
+            None => {
+                let _ = egl.terminate(display);
+                return Err(Error::Context(String::from("no suitable EGL config found")));
+            }
+        };
+        // Built before making it current so that a failure releases the EGL objects
+        let headless = HeadlessContext {
+            egl,
+            display,
+            surface,
+            context,
+        };
+        headless
+            .egl
+            .make_current(display, Some(surface), Some(surface), Some(context))
+            .map_err(|e| Error::Context(format!("cannot make context current: {}", e)))?;
+        Ok(headless)
+    }
+
+    pub fn get_proc_address(&self, name: &str) -> *const c_void {
+        self.egl
+            .get_proc_address(name)
+            .map_or(std::ptr::null(), |f| f as *const c_void)
+    }
+
+    pub fn swap_buffers(&self) {
+        self.egl
+            .swap_buffers(self.display, self.surface)
+            .expect("Cannot swap buffers");
+    }
+}
+
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_context(self.display, self.context);
+        let _ = self.egl.destroy_surface(self.display, self.surface);
+        let _ = self.egl.terminate(self.display);
+    }
+}
@@ -1,16 +1,38 @@
 extern crate glad_gles2;
 #[macro_use]
 extern crate log;
+extern crate base64;
 extern crate gltf;
+#[cfg(feature = "app")]
 extern crate glutin;
+extern crate image_crate;
+#[cfg(all(feature = "app", target_os = "linux"))]
+extern crate khronos_egl;
 extern crate nalgebra;
 
+pub mod animation;
+#[cfg(feature = "app")]
 pub mod app;
+pub mod clock;
+pub mod context;
+pub mod error;
+#[cfg(all(feature = "app", target_os = "linux"))]
+mod headless;
+pub mod resource;
 pub mod scene;
 pub mod shader;
 
-pub(crate) use app::{have_debug_gl, have_gl};
-
+pub use animation::{AnimationPlayer, BlendMode, PlaybackMode, PlaybackState};
+#[cfg(feature = "app")]
 pub use app::{Application, ApplicationAction, ApplicationOptions};
-pub use scene::import_scene;
+pub use clock::{Clock, FixedClock, KeyframeClock, RealtimeClock};
+pub use context::GlContext;
+pub use error::{Error, Result};
+pub use resource::{Buffer, Framebuffer, MemoryUsage, Texture, VertexArray};
+pub use scene::{
+    import_scene, import_scene_from_path, import_scene_from_path_with_options,
+    import_scene_with_options, try_import_scene, try_import_scene_from_path,
+    try_import_scene_from_path_with_options, try_import_scene_with_options, ImportOptions,
+    SceneSelector,
+};
 pub use shader::{Shader, ShaderType};
@@ -0,0 +1,244 @@
+use crate::context::GlContext;
+use glad_gles2::gl;
+use std::cell::Cell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Texture,
+    Buffer,
+    VertexArray,
+    Framebuffer,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub textures: ResourceUsage,
+    pub buffers: ResourceUsage,
+    pub vertex_arrays: ResourceUsage,
+    pub framebuffers: ResourceUsage,
+}
+
+impl MemoryUsage {
+    pub fn total_bytes(&self) -> usize {
+        self.textures.bytes
+            + self.buffers.bytes
+            + self.vertex_arrays.bytes
+            + self.framebuffers.bytes
+    }
+
+    pub(crate) fn category_mut(&mut self, category: Category) -> &mut ResourceUsage {
+        match category {
+            Category::Texture => &mut self.textures,
+            Category::Buffer => &mut self.buffers,
+            Category::VertexArray => &mut self.vertex_arrays,
+            Category::Framebuffer => &mut self.framebuffers,
+        }
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// The kinds of GL object a `Handle` can own, implemented by the `*Kind`
+/// types of this module only.
+pub trait ResourceKind: sealed::Sealed {
+    const CATEGORY: Category;
+
+    /// Generates a new object name.
+    ///
+    /// # Safety
+    ///
+    /// A GL context must be current and its functions loaded.
+    unsafe fn create() -> gl::GLuint;
+
+    /// Deletes an object generated by `create`.
+    ///
+    /// # Safety
+    ///
+    /// The context `name` was generated in must be current.
+    unsafe fn delete(name: gl::GLuint);
+}
+
+#[derive(Debug)]
+pub enum TextureKind {}
+#[derive(Debug)]
+pub enum BufferKind {}
+#[derive(Debug)]
+pub enum VertexArrayKind {}
+#[derive(Debug)]
+pub enum FramebufferKind {}
+
+impl sealed::Sealed for TextureKind {}
+impl sealed::Sealed for BufferKind {}
+impl sealed::Sealed for VertexArrayKind {}
+impl sealed::Sealed for FramebufferKind {}
+
+impl ResourceKind for TextureKind {
+    const CATEGORY: Category = Category::Texture;
+    unsafe fn create() -> gl::GLuint {
+        let mut name = 0;
+        gl::GenTextures(1, &mut name);
+        name
+    }
+    unsafe fn delete(name: gl::GLuint) {
+        gl::DeleteTextures(1, &name);
+    }
+}
+
+impl ResourceKind for BufferKind {
+    const CATEGORY: Category = Category::Buffer;
+    unsafe fn create() -> gl::GLuint {
+        let mut name = 0;
+        gl::GenBuffers(1, &mut name);
+        name
+    }
+    unsafe fn delete(name: gl::GLuint) {
+        gl::DeleteBuffers(1, &name);
+    }
+}
+
+impl ResourceKind for VertexArrayKind {
+    const CATEGORY: Category = Category::VertexArray;
+    unsafe fn create() -> gl::GLuint {
+        let mut name = 0;
+        gl::GenVertexArrays(1, &mut name);
+        name
+    }
+    unsafe fn delete(name: gl::GLuint) {
+        gl::DeleteVertexArrays(1, &name);
+    }
+}
+
+impl ResourceKind for FramebufferKind {
+    const CATEGORY: Category = Category::Framebuffer;
+    unsafe fn create() -> gl::GLuint {
+        let mut name = 0;
+        gl::GenFramebuffers(1, &mut name);
+        name
+    }
+    unsafe fn delete(name: gl::GLuint) {
+        gl::DeleteFramebuffers(1, &name);
+    }
+}
+
+struct RawResource<K: ResourceKind> {
+    gl: GlContext,
+    name: gl::GLuint,
+    bytes: Cell<usize>,
+    _kind: PhantomData<K>,
+}
+
+impl<K: ResourceKind> Drop for RawResource<K> {
+    fn drop(&mut self) {
+        self.gl
+            .account(K::CATEGORY, -1, -(self.bytes.get() as isize));
+        if self.gl.is_alive() {
+            unsafe {
+                K::delete(self.name);
+            }
+        }
+    }
+}
+
+/// Reference counted GL object: the object is deleted when the last clone
+/// of the handle is dropped.
+pub struct Handle<K: ResourceKind> {
+    inner: Rc<RawResource<K>>,
+}
+
+pub type Texture = Handle<TextureKind>;
+pub type Buffer = Handle<BufferKind>;
+pub type VertexArray = Handle<VertexArrayKind>;
+pub type Framebuffer = Handle<FramebufferKind>;
+
+impl<K: ResourceKind> Handle<K> {
+    pub fn new(gl: &GlContext) -> Self {
+        let name = unsafe { K::create() };
+        gl.account(K::CATEGORY, 1, 0);
+        Handle {
+            inner: Rc::new(RawResource {
+                gl: gl.clone(),
+                name,
+                bytes: Cell::new(0),
+                _kind: PhantomData,
+            }),
+        }
+    }
+
+    pub fn id(&self) -> gl::GLuint {
+        self.inner.name
+    }
+
+    pub fn size(&self) -> usize {
+        self.inner.bytes.get()
+    }
+
+    /// Records how many bytes of GPU memory the object's storage takes.
+    pub fn set_size(&self, bytes: usize) {
+        let old = self.inner.bytes.replace(bytes);
+        self.inner
+            .gl
+            .account(K::CATEGORY, 0, bytes as isize - old as isize);
+    }
+}
+
+impl<K: ResourceKind> Clone for Handle<K> {
+    fn clone(&self) -> Self {
+        Handle {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<K: ResourceKind> PartialEq for Handle<K> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl<K: ResourceKind> fmt::Debug for Handle<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("category", &K::CATEGORY)
+            .field("id", &self.inner.name)
+            .field("bytes", &self.inner.bytes.get())
+            .finish()
+    }
+}
+
+pub(crate) fn texel_size(internal_format: gl::GLenum) -> usize {
+    match internal_format {
+        gl::GL_R8 | gl::GL_RED => 1,
+        gl::GL_RG8 | gl::GL_R16F => 2,
+        gl::GL_RGB8 | gl::GL_RGB => 3,
+        gl::GL_RGBA8 | gl::GL_RGBA | gl::GL_RG16F | gl::GL_R32F | gl::GL_DEPTH_COMPONENT32F => 4,
+        gl::GL_DEPTH_COMPONENT24 => 4,
+        gl::GL_RGB16F => 6,
+        gl::GL_RGBA16F | gl::GL_RG32F => 8,
+        gl::GL_RGB32F => 12,
+        gl::GL_RGBA32F => 16,
+        _ => 4,
+    }
+}
+
+pub(crate) fn texture_size(internal_format: gl::GLenum, width: usize, height: usize) -> usize {
+    width * height * texel_size(internal_format)
+}
+
+pub(crate) fn mipmapped_texture_size(
+    internal_format: gl::GLenum,
+    width: usize,
+    height: usize,
+) -> usize {
+    texture_size(internal_format, width, height) * 4 / 3
+}
@@ -1,48 +1,61 @@
-use crate::have_gl;
+use crate::animation::{slerp, Animation, AnimationPlayer, Channel};
+use crate::clock::{Clock, KeyframeClock, RealtimeClock};
+use crate::context::GlContext;
+use crate::error::{Error, Result};
+use crate::resource::{
+    mipmapped_texture_size, texture_size, Buffer, Framebuffer, Texture, VertexArray,
+};
 use crate::shader::{Shader, ShaderType};
 use glad_gles2::gl;
-use gltf::animation::util::ReadOutputs;
-use gltf::buffer::Source;
 use gltf::image;
 use gltf::khr_lights_punctual::Kind;
-use na::geometry::{Perspective3, Point3, Quaternion, Similarity3, Translation3, UnitQuaternion};
+use gltf::material::AlphaMode;
+use na::geometry::{
+    Orthographic3, Perspective3, Point3, Quaternion, Similarity3, Translation3, UnitQuaternion,
+};
 use nalgebra as na;
-use std::cell::RefCell;
-use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
-use std::mem::size_of;
-use std::ops::Bound::Excluded;
-use std::ops::Bound::Included;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::mem::{size_of, size_of_val};
 use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
 use std::ptr::null;
 use std::rc::{Rc, Weak};
-use std::time::Instant;
 
 #[derive(Debug)]
 struct RenderPasses {
-    g_buffer: gl::GLuint,
-    g_position: gl::GLuint,
-    g_normal: gl::GLuint,
-    g_albedo: gl::GLuint,
-    g_metalness: gl::GLuint,
-    g_roughness: gl::GLuint,
-    g_depth: gl::GLuint,
-    g_pbr: gl::GLuint,
-    g_pbrfb: gl::GLuint,
-    g_ssr: gl::GLuint,
-    g_ssrfb: gl::GLuint,
-    g_ssra: gl::GLuint,
-    g_ssrafb: gl::GLuint,
-    q_vao: gl::GLuint,
-    q_vbo: gl::GLuint,
+    g_buffer: Framebuffer,
+    g_position: Texture,
+    g_normal: Texture,
+    g_albedo: Texture,
+    g_metalness: Texture,
+    g_roughness: Texture,
+    g_occlusion: Texture,
+    g_emissive: Texture,
+    g_depth: Texture,
+    g_pbr: Texture,
+    g_pbrfb: Framebuffer,
+    g_ssr: Texture,
+    g_ssrfb: Framebuffer,
+    g_ssra: Texture,
+    g_ssrafb: Framebuffer,
+    q_vao: VertexArray,
+    /// Only held so the quad vertices live as long as `q_vao`.
+    #[allow(dead_code)]
+    q_vbo: Buffer,
     r_rgb: Shader,
     r_r: Shader,
 }
 
 #[derive(Debug)]
 pub struct Scene {
-    root: SceneNode,
-    camera: SceneNode,
-    lights: Vec<SceneNode>,
+    gl: GlContext,
+    graphs: Vec<SceneGraph>,
+    active_scene: usize,
+    active_camera: usize,
     width: u32,
     height: u32,
     passes: RenderPasses,
@@ -50,18 +63,19 @@ pub struct Scene {
     pbr_shader: Shader,
     ssr_shader: Shader,
     ssr_apply_shader: Shader,
+    forward_shader: Shader,
     fps: VecDeque<f64>,
     fps_total: f64,
-    last_frame_time: Instant,
-    animation: HashMap<usize, BTreeMap<u64, Transform>>,
-    animation_step: Vec<u64>,
-    start_time: Instant,
-    frame_count: usize,
+    player: AnimationPlayer,
+    clock: Box<dyn Clock>,
+    keyframes: KeyframeClock,
+    time: Option<f64>,
+    output_framebuffer: gl::GLuint,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct Transform {
-    pub scaling: Option<f32>,
+    pub scaling: Option<na::Vector3<f32>>,
     pub translation: Option<Translation3<f32>>,
     pub rotation: Option<UnitQuaternion<f32>>,
 }
@@ -69,44 +83,72 @@ pub struct Transform {
 impl Transform {
     pub fn identity() -> Self {
         Transform {
-            scaling: Some(1.0),
+            scaling: Some(na::Vector3::new(1.0, 1.0, 1.0)),
             translation: Some(Translation3::<f32>::identity()),
             rotation: Some(UnitQuaternion::<f32>::identity()),
         }
     }
 
+    /// The local matrix, scaling first, then rotation and translation.
+    pub fn matrix(&self) -> na::Matrix4<f32> {
+        self.translation.unwrap().to_homogeneous()
+            * self.rotation.unwrap().to_homogeneous()
+            * na::Matrix4::new_nonuniform_scaling(&self.scaling.unwrap())
+    }
+
+    /// Splits a matrix without shear back into scaling, rotation and
+    /// translation.
+    fn from_matrix(matrix: &na::Matrix4<f32>) -> Transform {
+        let column = |i| na::Vector3::new(matrix[(0, i)], matrix[(1, i)], matrix[(2, i)]);
+        let scaling = na::Vector3::new(column(0).norm(), column(1).norm(), column(2).norm());
+        let rotation = na::Matrix3::from_columns(&[
+            column(0) / scaling.x,
+            column(1) / scaling.y,
+            column(2) / scaling.z,
+        ]);
+        Transform {
+            scaling: Some(scaling),
+            rotation: Some(UnitQuaternion::from_rotation_matrix(
+                &na::Rotation3::from_matrix_unchecked(rotation),
+            )),
+            translation: Some(Translation3::from(column(3))),
+        }
+    }
+
+    /// Per-axis scaling is averaged into a uniform one.
+    #[deprecated(note = "similarities can't hold per-axis scaling, use `matrix`")]
     pub fn get_similarity(&self) -> Similarity3<f32> {
+        let transform = Transform::from_matrix(&self.matrix());
+        let scaling = transform.scaling.unwrap();
         Similarity3::<f32>::from_parts(
-            self.translation.unwrap(),
-            self.rotation.unwrap(),
-            self.scaling.unwrap(),
+            transform.translation.unwrap(),
+            transform.rotation.unwrap(),
+            (scaling.x * scaling.y * scaling.z).cbrt(),
         )
     }
 
+    /// Only exact when the product has no shear.
+    #[deprecated(note = "compose the matrices from `matrix` instead")]
     pub fn compose(&self, other: Transform) -> Transform {
-        let result = self.get_similarity() * other.get_similarity();
-        Transform {
-            scaling: Some(result.scaling()),
-            rotation: Some(result.isometry.rotation),
-            translation: Some(result.isometry.translation),
-        }
+        Transform::from_matrix(&(self.matrix() * other.matrix()))
     }
 
+    /// Interpolates the properties both transforms have, rotations the short
+    /// way around, and keeps the ones only one of them has.
     pub fn lerp(&self, other: Transform, alpha: f32) -> Transform {
-        let scaling = Some(self.scaling.unwrap() * (1.0 - alpha) + other.scaling.unwrap() * alpha);
-        let rotation = Some(
-            self.rotation
-                .unwrap()
-                .slerp(&other.rotation.unwrap(), alpha),
-        );
-        let translation = Some(Translation3::<f32>::from(
-            self.translation.unwrap().vector * (1.0 - alpha)
-                + other.translation.unwrap().vector * alpha,
-        ));
         Transform {
-            scaling,
-            rotation,
-            translation,
+            scaling: match (self.scaling, other.scaling) {
+                (Some(a), Some(b)) => Some(a.lerp(&b, alpha)),
+                (a, b) => b.or(a),
+            },
+            rotation: match (self.rotation, other.rotation) {
+                (Some(a), Some(b)) => Some(slerp(&a, &b, alpha)),
+                (a, b) => b.or(a),
+            },
+            translation: match (self.translation, other.translation) {
+                (Some(a), Some(b)) => Some(Translation3::from(a.vector.lerp(&b.vector, alpha))),
+                (a, b) => b.or(a),
+            },
         }
     }
 
@@ -138,13 +180,88 @@ pub struct RealSceneNode {
     name: String,
     pub camera: Option<Camera>,
     pub light: Option<Light>,
-    pub mesh: Option<Mesh>,
+    pub mesh: Option<Rc<Mesh>>,
+    pub skin: Option<Rc<Skin>>,
+    /// Morph target weights of the mesh, replaced by animated values while a
+    /// weights channel targets the node.
+    pub weights: Vec<f32>,
+}
+
+/// Node hierarchy of one of the scenes in the document.
+#[derive(Debug)]
+struct SceneGraph {
+    name: String,
+    root: SceneNode,
+    cameras: Vec<SceneNode>,
+    lights: Vec<SceneNode>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Projection {
+    Perspective(Perspective3<f32>),
+    Orthographic(Orthographic3<f32>),
+}
+
+impl Projection {
+    pub fn is_orthographic(&self) -> bool {
+        match self {
+            Projection::Perspective(_) => false,
+            Projection::Orthographic(_) => true,
+        }
+    }
+
+    /// Matches the projection to a viewport, orthographic projections keep
+    /// their vertical extent.
+    pub fn set_aspect(&mut self, aspect: f32) {
+        match self {
+            Projection::Perspective(p) => p.set_aspect(aspect),
+            Projection::Orthographic(o) => {
+                let xmag = (o.top() - o.bottom()) / 2.0 * aspect;
+                o.set_left_and_right(-xmag, xmag);
+            }
+        }
+    }
+
+    pub fn to_homogeneous(&self) -> na::Matrix4<f32> {
+        match self {
+            Projection::Perspective(p) => p.to_homogeneous(),
+            Projection::Orthographic(o) => o.to_homogeneous(),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Camera {
-    perspective: Perspective3<f32>,
+    projection: Projection,
     name: String,
+    /// Aspect ratio set in the document, the viewport's is used otherwise.
+    aspect_ratio: Option<f32>,
+}
+
+impl Camera {
+    /// Matches the projection to a viewport of the given aspect ratio,
+    /// unless the document fixes one.
+    fn fit(&mut self, aspect: f32) {
+        self.projection
+            .set_aspect(self.aspect_ratio.unwrap_or(aspect));
+    }
+}
+
+/// Camera placement and projection a frame is rendered with.
+struct View {
+    position: Point3<f32>,
+    direction: na::Vector3<f32>,
+    orthographic: bool,
+    matrix: na::Matrix4<f32>,
+}
+
+impl View {
+    fn set_uniforms(&self, shader: &mut Shader) {
+        let (p, d) = (self.position, self.direction);
+        shader.uniform3f("camera_pos", [p[0], p[1], p[2]]);
+        shader.uniform3f("camera_dir", [d[0], d[1], d[2]]);
+        shader.uniform1i("camera_orthographic", self.orthographic as i32);
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -157,66 +274,182 @@ pub struct Light {
 
 #[derive(Debug)]
 pub struct RenderData {
-    vao: gl::GLuint,
-    vbo: gl::GLuint,
+    vao: VertexArray,
+    vbo: Buffer,
+    ebo: Option<Buffer>,
+    skin_vbo: Option<Buffer>,
+    morph: Option<MorphTargets>,
     mode: gl::GLuint,
-    pub material: Material,
-    buffer: Vec<f32>,
+    index_type: gl::GLenum,
+    pub material: Rc<RefCell<Material>>,
+    vertices: Option<Vec<f32>>,
+    indices: Option<Vec<u32>>,
     n_elements: i32,
+    center: Point3<f32>,
 }
 
 impl RenderData {
-    pub fn draw(&self, shader: &mut Shader) {
-        shader.uniform1i("material.albedo_sampler", 0);
-        shader.uniform4f("material.albedo", self.material.color);
-        shader.uniform1f("material.metalness", self.material.metallic);
-        shader.uniform1f("material.roughness", self.material.roughness);
+    /// Draws the primitive displaced by its morph targets at `weights`.
+    pub fn draw(&self, shader: &mut Shader, weights: &[f32]) {
+        let material = self.material.borrow();
+        shader.uniform4f("material.albedo", material.color);
+        shader.uniform1f("material.metalness", material.metallic);
+        shader.uniform1f("material.roughness", material.roughness);
+        shader.uniform1i("material.has_normal", material.normal.is_some() as i32);
+        shader.uniform1f("material.normal_scale", material.normal_scale);
+        shader.uniform1f("material.occlusion_strength", material.occlusion_strength);
+        shader.uniform3f("material.emissive", material.emissive_factor);
+        shader.uniform1i("skinned", self.skin_vbo.is_some() as i32);
+        shader.uniform1i(
+            "material.alpha_mode",
+            match material.alpha_mode {
+                AlphaMode::Opaque => 0,
+                AlphaMode::Mask => 1,
+                AlphaMode::Blend => 2,
+            },
+        );
+        shader.uniform1f("material.alpha_cutoff", material.alpha_cutoff);
+        shader.uniform1i("material.double_sided", material.double_sided as i32);
         unsafe {
+            if material.double_sided {
+                gl::Disable(gl::GL_CULL_FACE);
+            } else {
+                gl::Enable(gl::GL_CULL_FACE);
+                gl::CullFace(gl::GL_BACK);
+            }
+            let slots = [
+                ("albedo", &material.albedo),
+                ("normal", &material.normal),
+                ("metallic_roughness", &material.metallic_roughness),
+                ("occlusion", &material.occlusion),
+                ("emissive", &material.emissive),
+            ];
+            for (i, (name, slot)) in slots.iter().enumerate() {
+                let (id, tex_coord, transform) = match slot {
+                    Some(slot) => (slot.texture.id(), slot.tex_coord, slot.matrix()),
+                    None => (0, 0, na::Matrix3::identity()),
+                };
+                shader.uniform1i(&format!("material.{}_sampler", name), i as i32);
+                shader.uniform1i(&format!("material.{}_uv", name), tex_coord as i32);
+                shader.uniformMat3f(&format!("material.{}_transform", name), transform.into());
+                gl::ActiveTexture(gl::GL_TEXTURE0 + i as gl::GLenum);
+                gl::BindTexture(gl::GL_TEXTURE_2D, id);
+            }
+            self.bind_morph(shader, weights);
             gl::ActiveTexture(gl::GL_TEXTURE0);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.material.albedo);
-            gl::BindVertexArray(self.vao);
-            gl::DrawArrays(self.mode, 0, self.n_elements);
+            gl::BindVertexArray(self.vao.id());
+            if self.ebo.is_some() {
+                gl::DrawElements(self.mode, self.n_elements, self.index_type, null());
+            } else {
+                gl::DrawArrays(self.mode, 0, self.n_elements);
+            }
+        }
+    }
+
+    /// Blends up to `MAX_ACTIVE_MORPH_TARGETS` targets in the vertex shader,
+    /// more than that, or targets without a texture, are blended here and
+    /// uploaded over the vertex buffer.
+    fn bind_morph(&self, shader: &mut Shader, weights: &[f32]) {
+        let morph = match &self.morph {
+            Some(morph) => morph,
+            None => {
+                shader.uniform1i("morphed", 0);
+                return;
+            }
+        };
+        let active = active_morph_targets(weights);
+        match &morph.texture {
+            Some(texture) if active.len() <= MAX_ACTIVE_MORPH_TARGETS => {
+                if let Some(cpu) = morph.cpu.as_ref().filter(|cpu| cpu.dirty.get()) {
+                    self.upload_vertices(&cpu.base);
+                    cpu.dirty.set(false);
+                }
+                shader.uniform1i("morphed", 1);
+                shader.uniform1i("morph_sampler", 6);
+                shader.uniform1i("morph_rows", morph.rows as i32);
+                shader.uniform1i("n_morph_weights", active.len() as i32);
+                for (i, (target, weight)) in active.iter().enumerate() {
+                    shader.uniform1i(&format!("morph_targets[{}]", i), *target as i32);
+                    shader.uniform1f(&format!("morph_weights[{}]", i), *weight);
+                }
+                unsafe {
+                    gl::ActiveTexture(gl::GL_TEXTURE6);
+                    gl::BindTexture(gl::GL_TEXTURE_2D, texture.id());
+                }
+            }
+            _ => {
+                shader.uniform1i("morphed", 0);
+                let cpu = morph
+                    .cpu
+                    .as_ref()
+                    .expect("morph targets without a CPU copy");
+                if !active.is_empty() {
+                    self.upload_vertices(&blend_morph_targets(&cpu.base, &cpu.deltas, &active));
+                    cpu.dirty.set(true);
+                } else if cpu.dirty.get() {
+                    self.upload_vertices(&cpu.base);
+                    cpu.dirty.set(false);
+                }
+            }
+        }
+    }
+
+    fn upload_vertices(&self, vertices: &[f32]) {
+        unsafe {
+            gl::BindBuffer(gl::GL_ARRAY_BUFFER, self.vbo.id());
+            gl::BufferSubData(
+                gl::GL_ARRAY_BUFFER,
+                0,
+                size_of_val(vertices) as isize,
+                vertices.as_ptr() as *const c_void,
+            );
+            gl::BindBuffer(gl::GL_ARRAY_BUFFER, 0);
         }
     }
+
+    /// Interleaved vertex data as laid out in `VERTEX_LAYOUT`, only kept when the
+    /// scene was imported with `ImportOptions::keep_vertex_data`.
+    pub fn vertices(&self) -> Option<&[f32]> {
+        self.vertices.as_deref()
+    }
+
+    pub fn indices(&self) -> Option<&[u32]> {
+        self.indices.as_deref()
+    }
+
+    pub fn morph_targets(&self) -> usize {
+        self.morph.as_ref().map_or(0, |morph| morph.count)
+    }
+
+    /// Blended primitives skip the G-buffer and are drawn by the forward pass.
+    pub fn is_transparent(&self) -> bool {
+        self.material.borrow().alpha_mode == AlphaMode::Blend
+    }
 }
 
 impl RenderPasses {
-    pub fn new(width: gl::GLsizei, height: gl::GLsizei) -> RenderPasses {
-        let mut g_buffer: gl::GLuint = 0;
-        let mut g_position: gl::GLuint = 0;
-        let mut g_normal: gl::GLuint = 0;
-        let mut g_albedo: gl::GLuint = 0;
-        let mut g_metalness: gl::GLuint = 0;
-        let mut g_roughness: gl::GLuint = 0;
-        let mut g_depth: gl::GLuint = 0;
-        let mut g_pbr: gl::GLuint = 0;
-        let mut g_pbrfb: gl::GLuint = 0;
-        let mut g_ssr: gl::GLuint = 0;
-        let mut g_ssrfb: gl::GLuint = 0;
-        let mut g_ssra: gl::GLuint = 0;
-        let mut g_ssrafb: gl::GLuint = 0;
-        let mut q_vao: gl::GLuint = 0;
-        let mut q_vbo: gl::GLuint = 0;
+    pub fn new(gl: &GlContext, width: gl::GLsizei, height: gl::GLsizei) -> Result<RenderPasses> {
+        let g_buffer = Framebuffer::new(gl);
+        let g_position = Texture::new(gl);
+        let g_normal = Texture::new(gl);
+        let g_albedo = Texture::new(gl);
+        let g_metalness = Texture::new(gl);
+        let g_roughness = Texture::new(gl);
+        let g_occlusion = Texture::new(gl);
+        let g_emissive = Texture::new(gl);
+        let g_depth = Texture::new(gl);
+        let g_pbr = Texture::new(gl);
+        let g_pbrfb = Framebuffer::new(gl);
+        let g_ssr = Texture::new(gl);
+        let g_ssrfb = Framebuffer::new(gl);
+        let g_ssra = Texture::new(gl);
+        let g_ssrafb = Framebuffer::new(gl);
+        let q_vao = VertexArray::new(gl);
+        let q_vbo = Buffer::new(gl);
         unsafe {
-            gl::GenFramebuffers(1, &mut g_buffer);
-            gl::GenFramebuffers(1, &mut g_pbrfb);
-            gl::GenFramebuffers(1, &mut g_ssrfb);
-            gl::GenFramebuffers(1, &mut g_ssrafb);
-            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, g_buffer);
-            let bind = |buf, internal_format, format, kind, attachment| {
-                gl::GenTextures(1, buf);
-                gl::BindTexture(gl::GL_TEXTURE_2D, *buf);
-                gl::TexImage2D(
-                    gl::GL_TEXTURE_2D,
-                    0,
-                    internal_format as gl::GLint,
-                    width,
-                    height,
-                    0,
-                    format,
-                    kind,
-                    null(),
-                );
+            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, g_buffer.id());
+            let bind = |buf: &Texture, attachment| {
+                gl::BindTexture(gl::GL_TEXTURE_2D, buf.id());
                 gl::TexParameteri(
                     gl::GL_TEXTURE_2D,
                     gl::GL_TEXTURE_MIN_FILTER,
@@ -241,87 +474,46 @@ impl RenderPasses {
                     gl::GL_FRAMEBUFFER,
                     attachment,
                     gl::GL_TEXTURE_2D,
-                    *buf,
+                    buf.id(),
                     0,
                 );
             };
-            bind(
-                &mut g_position,
-                gl::GL_RGB16F,
-                gl::GL_RGB,
-                gl::GL_HALF_FLOAT,
-                gl::GL_COLOR_ATTACHMENT0,
-            );
-            bind(
-                &mut g_normal,
-                gl::GL_RGB16F,
-                gl::GL_RGB,
-                gl::GL_HALF_FLOAT,
-                gl::GL_COLOR_ATTACHMENT1,
-            );
-            bind(
-                &mut g_albedo,
-                gl::GL_RGB,
-                gl::GL_RGB,
-                gl::GL_UNSIGNED_BYTE,
-                gl::GL_COLOR_ATTACHMENT2,
-            );
-            bind(
-                &mut g_metalness,
-                gl::GL_R16F,
-                gl::GL_RED,
-                gl::GL_HALF_FLOAT,
-                gl::GL_COLOR_ATTACHMENT3,
-            );
-            bind(
-                &mut g_roughness,
-                gl::GL_R16F,
-                gl::GL_RED,
-                gl::GL_HALF_FLOAT,
-                gl::GL_COLOR_ATTACHMENT4,
-            );
-            bind(
-                &mut g_depth,
-                gl::GL_DEPTH_COMPONENT32F,
-                gl::GL_DEPTH_COMPONENT,
-                gl::GL_FLOAT,
-                gl::GL_DEPTH_ATTACHMENT,
-            );
+            bind(&g_position, gl::GL_COLOR_ATTACHMENT0);
+            bind(&g_normal, gl::GL_COLOR_ATTACHMENT1);
+            bind(&g_albedo, gl::GL_COLOR_ATTACHMENT2);
+            bind(&g_metalness, gl::GL_COLOR_ATTACHMENT3);
+            bind(&g_roughness, gl::GL_COLOR_ATTACHMENT4);
+            bind(&g_occlusion, gl::GL_COLOR_ATTACHMENT5);
+            bind(&g_emissive, gl::GL_COLOR_ATTACHMENT6);
+            bind(&g_depth, gl::GL_DEPTH_ATTACHMENT);
             let draw_buffers = [
                 gl::GL_COLOR_ATTACHMENT0,
                 gl::GL_COLOR_ATTACHMENT1,
                 gl::GL_COLOR_ATTACHMENT2,
                 gl::GL_COLOR_ATTACHMENT3,
                 gl::GL_COLOR_ATTACHMENT4,
+                gl::GL_COLOR_ATTACHMENT5,
+                gl::GL_COLOR_ATTACHMENT6,
             ];
-            gl::DrawBuffers(5, draw_buffers.as_ptr());
-            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, g_pbrfb);
-            bind(
-                &mut g_pbr,
-                gl::GL_RGB,
-                gl::GL_RGB,
-                gl::GL_UNSIGNED_BYTE,
-                gl::GL_COLOR_ATTACHMENT0,
-            );
+            gl::DrawBuffers(7, draw_buffers.as_ptr());
+            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, g_pbrfb.id());
+            bind(&g_pbr, gl::GL_COLOR_ATTACHMENT0);
             let draw_buffers = [gl::GL_COLOR_ATTACHMENT0];
             gl::DrawBuffers(1, draw_buffers.as_ptr());
-            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, g_ssrfb);
-            bind(
-                &mut g_ssr,
-                gl::GL_RGB,
-                gl::GL_RGB,
-                gl::GL_UNSIGNED_BYTE,
-                gl::GL_COLOR_ATTACHMENT0,
-            );
+            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, g_ssrfb.id());
+            bind(&g_ssr, gl::GL_COLOR_ATTACHMENT0);
             let draw_buffers = [gl::GL_COLOR_ATTACHMENT0];
             gl::DrawBuffers(1, draw_buffers.as_ptr());
-            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, g_ssrafb);
-            bind(
-                &mut g_ssra,
-                gl::GL_RGB,
-                gl::GL_RGB,
-                gl::GL_UNSIGNED_BYTE,
-                gl::GL_COLOR_ATTACHMENT0,
+            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, g_ssrafb.id());
+            bind(&g_ssra, gl::GL_COLOR_ATTACHMENT0);
+            // The forward pass depth tests transparent geometry against the
+            // opaque G-buffer depth
+            gl::FramebufferTexture2D(
+                gl::GL_FRAMEBUFFER,
+                gl::GL_DEPTH_ATTACHMENT,
+                gl::GL_TEXTURE_2D,
+                g_depth.id(),
+                0,
             );
             let draw_buffers = [gl::GL_COLOR_ATTACHMENT0];
             gl::DrawBuffers(1, draw_buffers.as_ptr());
@@ -335,16 +527,15 @@ impl RenderPasses {
              1.0,  1.0, 1.0, 1.0
         ];
         unsafe {
-            gl::GenVertexArrays(1, &mut q_vao);
-            gl::GenBuffers(1, &mut q_vbo);
-            gl::BindVertexArray(q_vao);
-            gl::BindBuffer(gl::GL_ARRAY_BUFFER, q_vbo);
+            gl::BindVertexArray(q_vao.id());
+            gl::BindBuffer(gl::GL_ARRAY_BUFFER, q_vbo.id());
             gl::BufferData(
                 gl::GL_ARRAY_BUFFER,
                 (quad.len() * size_of::<gl::GLfloat>()) as isize,
                 quad.as_ptr() as *const c_void,
                 gl::GL_STATIC_DRAW,
             );
+            q_vbo.set_size(quad.len() * size_of::<gl::GLfloat>());
             gl::VertexAttribPointer(
                 0,
                 2,
@@ -366,21 +557,39 @@ impl RenderPasses {
             gl::BindBuffer(gl::GL_ARRAY_BUFFER, 0);
             gl::BindVertexArray(0);
         }
-        let mut r_rgb = Shader::new();
-        let mut r_r = Shader::new();
-        r_rgb.attach(include_str!("shaders/quad.vert"), ShaderType::Vertex);
-        r_rgb.attach(include_str!("shaders/quad_rgb.frag"), ShaderType::Fragment);
-        r_rgb.compile();
-        r_r.attach(include_str!("shaders/quad.vert"), ShaderType::Vertex);
-        r_r.attach(include_str!("shaders/quad_r.frag"), ShaderType::Fragment);
-        r_r.compile();
-        RenderPasses {
+        let mut r_rgb = Shader::new(gl);
+        let mut r_r = Shader::new(gl);
+        r_rgb.try_attach_with_name(
+            include_str!("shaders/quad.vert"),
+            ShaderType::Vertex,
+            "quad.vert",
+        )?;
+        r_rgb.try_attach_with_name(
+            include_str!("shaders/quad_rgb.frag"),
+            ShaderType::Fragment,
+            "quad_rgb.frag",
+        )?;
+        r_rgb.try_compile_with_name("quad_rgb")?;
+        r_r.try_attach_with_name(
+            include_str!("shaders/quad.vert"),
+            ShaderType::Vertex,
+            "quad.vert",
+        )?;
+        r_r.try_attach_with_name(
+            include_str!("shaders/quad_r.frag"),
+            ShaderType::Fragment,
+            "quad_r.frag",
+        )?;
+        r_r.try_compile_with_name("quad_r")?;
+        let passes = RenderPasses {
             g_buffer,
             g_position,
             g_normal,
             g_albedo,
             g_metalness,
             g_roughness,
+            g_occlusion,
+            g_emissive,
             g_depth,
             q_vao,
             q_vbo,
@@ -392,18 +601,95 @@ impl RenderPasses {
             g_ssrfb,
             g_ssra,
             g_ssrafb,
+        };
+        passes.resize(width, height);
+        Ok(passes)
+    }
+
+    pub fn resize(&self, width: gl::GLsizei, height: gl::GLsizei) {
+        let targets = [
+            (
+                &self.g_position,
+                gl::GL_RGB16F,
+                gl::GL_RGB,
+                gl::GL_HALF_FLOAT,
+            ),
+            (&self.g_normal, gl::GL_RGB16F, gl::GL_RGB, gl::GL_HALF_FLOAT),
+            (&self.g_albedo, gl::GL_RGB, gl::GL_RGB, gl::GL_UNSIGNED_BYTE),
+            (
+                &self.g_metalness,
+                gl::GL_R16F,
+                gl::GL_RED,
+                gl::GL_HALF_FLOAT,
+            ),
+            (
+                &self.g_roughness,
+                gl::GL_R16F,
+                gl::GL_RED,
+                gl::GL_HALF_FLOAT,
+            ),
+            (
+                &self.g_occlusion,
+                gl::GL_R16F,
+                gl::GL_RED,
+                gl::GL_HALF_FLOAT,
+            ),
+            (
+                &self.g_emissive,
+                gl::GL_RGB16F,
+                gl::GL_RGB,
+                gl::GL_HALF_FLOAT,
+            ),
+            (
+                &self.g_depth,
+                gl::GL_DEPTH_COMPONENT32F,
+                gl::GL_DEPTH_COMPONENT,
+                gl::GL_FLOAT,
+            ),
+            (&self.g_pbr, gl::GL_RGB, gl::GL_RGB, gl::GL_UNSIGNED_BYTE),
+            (&self.g_ssr, gl::GL_RGB, gl::GL_RGB, gl::GL_UNSIGNED_BYTE),
+            (&self.g_ssra, gl::GL_RGB, gl::GL_RGB, gl::GL_UNSIGNED_BYTE),
+        ];
+        unsafe {
+            for (texture, internal_format, format, kind) in targets.iter() {
+                gl::BindTexture(gl::GL_TEXTURE_2D, texture.id());
+                gl::TexImage2D(
+                    gl::GL_TEXTURE_2D,
+                    0,
+                    *internal_format as gl::GLint,
+                    width,
+                    height,
+                    0,
+                    *format,
+                    *kind,
+                    null(),
+                );
+                texture.set_size(texture_size(
+                    *internal_format,
+                    width as usize,
+                    height as usize,
+                ));
+            }
+            gl::BindTexture(gl::GL_TEXTURE_2D, 0);
         }
+        // The PBR output gets a full mipmap chain every frame for the SSR pass
+        self.g_pbr.set_size(mipmapped_texture_size(
+            gl::GL_RGB,
+            width as usize,
+            height as usize,
+        ));
     }
+
     pub fn bind(&self) {
         unsafe {
-            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, self.g_buffer);
+            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, self.g_buffer.id());
             gl::Clear(gl::GL_COLOR_BUFFER_BIT | gl::GL_DEPTH_BUFFER_BIT);
         }
     }
 
     pub fn bind_pbr(&self, shader: &mut Shader) {
         unsafe {
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_pbr);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_pbr.id());
             gl::TexParameteri(
                 gl::GL_TEXTURE_2D,
                 gl::GL_TEXTURE_MIN_FILTER,
@@ -415,30 +701,36 @@ impl RenderPasses {
                 gl::GL_NEAREST as gl::GLint,
             );
             gl::BindTexture(gl::GL_TEXTURE_2D, 0);
-            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, self.g_pbrfb);
+            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, self.g_pbrfb.id());
             gl::Clear(gl::GL_COLOR_BUFFER_BIT | gl::GL_DEPTH_BUFFER_BIT);
             shader.uniform1i("position_sampler", 0);
             shader.uniform1i("normal_sampler", 1);
             shader.uniform1i("albedo_sampler", 2);
             shader.uniform1i("metalness_sampler", 3);
             shader.uniform1i("roughness_sampler", 4);
+            shader.uniform1i("occlusion_sampler", 5);
+            shader.uniform1i("emissive_sampler", 6);
             gl::ActiveTexture(gl::GL_TEXTURE0);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_position);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_position.id());
             gl::ActiveTexture(gl::GL_TEXTURE1);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_normal);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_normal.id());
             gl::ActiveTexture(gl::GL_TEXTURE2);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_albedo);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_albedo.id());
             gl::ActiveTexture(gl::GL_TEXTURE3);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_metalness);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_metalness.id());
             gl::ActiveTexture(gl::GL_TEXTURE4);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_roughness);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_roughness.id());
+            gl::ActiveTexture(gl::GL_TEXTURE5);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_occlusion.id());
+            gl::ActiveTexture(gl::GL_TEXTURE6);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_emissive.id());
             gl::ActiveTexture(gl::GL_TEXTURE0);
         }
     }
 
     pub fn bind_ssr(&self, shader: &mut Shader) {
         unsafe {
-            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, self.g_ssrfb);
+            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, self.g_ssrfb.id());
             gl::Clear(gl::GL_COLOR_BUFFER_BIT | gl::GL_DEPTH_BUFFER_BIT);
             shader.uniform1i("position_sampler", 0);
             shader.uniform1i("normal_sampler", 1);
@@ -447,11 +739,11 @@ impl RenderPasses {
             shader.uniform1i("roughness_sampler", 4);
             shader.uniform1i("depth_sampler", 5);
             gl::ActiveTexture(gl::GL_TEXTURE0);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_position);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_position.id());
             gl::ActiveTexture(gl::GL_TEXTURE1);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_normal);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_normal.id());
             gl::ActiveTexture(gl::GL_TEXTURE2);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_pbr);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_pbr.id());
             gl::GenerateMipmap(gl::GL_TEXTURE_2D);
             gl::TexParameteri(
                 gl::GL_TEXTURE_2D,
@@ -464,44 +756,62 @@ impl RenderPasses {
                 gl::GL_LINEAR as gl::GLint,
             );
             gl::ActiveTexture(gl::GL_TEXTURE3);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_metalness);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_metalness.id());
             gl::ActiveTexture(gl::GL_TEXTURE4);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_roughness);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_roughness.id());
             gl::ActiveTexture(gl::GL_TEXTURE5);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_depth);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_depth.id());
             gl::ActiveTexture(gl::GL_TEXTURE0);
         }
     }
 
     pub fn bind_ssr_apply(&self, shader: &mut Shader) {
         unsafe {
-            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, self.g_ssrafb);
-            gl::Clear(gl::GL_COLOR_BUFFER_BIT | gl::GL_DEPTH_BUFFER_BIT);
+            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, self.g_ssrafb.id());
+            gl::Disable(gl::GL_DEPTH_TEST);
+            gl::Clear(gl::GL_COLOR_BUFFER_BIT);
             shader.uniform1i("ssr_sampler", 0);
             shader.uniform1i("metalness_sampler", 1);
             shader.uniform1i("pbr_sampler", 2);
             gl::ActiveTexture(gl::GL_TEXTURE0);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_ssr);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_ssr.id());
             gl::ActiveTexture(gl::GL_TEXTURE1);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_metalness);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_metalness.id());
             gl::ActiveTexture(gl::GL_TEXTURE2);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_pbr);
-            gl::ActiveTexture(gl::GL_TEXTURE0);
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.g_pbr.id());
             gl::ActiveTexture(gl::GL_TEXTURE0);
         }
     }
 
+    pub fn bind_forward(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, self.g_ssrafb.id());
+            gl::Enable(gl::GL_DEPTH_TEST);
+            gl::DepthMask(gl::GL_FALSE);
+            gl::Enable(gl::GL_BLEND);
+            gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE_MINUS_SRC_ALPHA);
+        }
+    }
+
+    pub fn unbind_forward(&self) {
+        unsafe {
+            gl::Disable(gl::GL_CULL_FACE);
+            gl::Disable(gl::GL_BLEND);
+            gl::DepthMask(gl::GL_TRUE);
+        }
+    }
+
     pub fn print_quad(&self) {
         unsafe {
-            gl::BindVertexArray(self.q_vao);
+            gl::BindVertexArray(self.q_vao.id());
             gl::DrawArrays(gl::GL_TRIANGLE_STRIP, 0, 4);
             gl::BindVertexArray(0);
         }
     }
 
-    pub fn print_buffer(&self, name: &str) {
+    pub fn print_buffer(&self, name: &str, framebuffer: gl::GLuint) {
         unsafe {
-            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, 0);
+            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, framebuffer);
             gl::Clear(gl::GL_COLOR_BUFFER_BIT | gl::GL_DEPTH_BUFFER_BIT);
         }
         let info = match name {
@@ -510,6 +820,8 @@ impl RenderPasses {
             "albedo" => (&self.g_albedo, &self.r_rgb),
             "metalness" => (&self.g_metalness, &self.r_r),
             "roughness" => (&self.g_roughness, &self.r_r),
+            "occlusion" => (&self.g_occlusion, &self.r_r),
+            "emissive" => (&self.g_emissive, &self.r_rgb),
             "depth" => (&self.g_depth, &self.r_r),
             "pbr" => (&self.g_pbr, &self.r_rgb),
             "ssr" => (&self.g_ssr, &self.r_rgb),
@@ -518,7 +830,7 @@ impl RenderPasses {
         };
         info.1.activate();
         unsafe {
-            gl::BindTexture(gl::GL_TEXTURE_2D, *info.0);
+            gl::BindTexture(gl::GL_TEXTURE_2D, info.0.id());
         }
         self.print_quad();
         unsafe {
@@ -527,6 +839,123 @@ impl RenderPasses {
     }
 }
 
+/// Joints of a skin along with the texture their matrices are streamed
+/// through, one joint per row of four RGBA32F texels.
+#[derive(Debug)]
+pub struct Skin {
+    name: String,
+    joints: Vec<usize>,
+    inverse_bind_matrices: Vec<na::Matrix4<f32>>,
+    texture: Texture,
+}
+
+impl Skin {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Indices of the joint nodes, in the order `JOINTS_0` refers to them.
+    pub fn joints(&self) -> &[usize] {
+        &self.joints
+    }
+
+    /// Uploads the joint matrices given the world matrix of every node.
+    fn update(&self, worlds: &HashMap<usize, na::Matrix4<f32>>) {
+        let matrices: Vec<f32> = self
+            .joints
+            .iter()
+            .zip(&self.inverse_bind_matrices)
+            .flat_map(|(joint, inverse_bind)| {
+                let world = worlds
+                    .get(joint)
+                    .copied()
+                    .unwrap_or_else(na::Matrix4::identity);
+                (world * inverse_bind).as_slice().to_vec()
+            })
+            .collect();
+        unsafe {
+            gl::BindTexture(gl::GL_TEXTURE_2D, self.texture.id());
+            gl::TexImage2D(
+                gl::GL_TEXTURE_2D,
+                0,
+                gl::GL_RGBA32F as i32,
+                4,
+                self.joints.len() as i32,
+                0,
+                gl::GL_RGBA,
+                gl::GL_FLOAT,
+                matrices.as_ptr() as *const c_void,
+            );
+            gl::BindTexture(gl::GL_TEXTURE_2D, 0);
+        }
+        self.texture
+            .set_size(texture_size(gl::GL_RGBA32F, 4, self.joints.len()));
+    }
+}
+
+/// Position and normal displacements of the morph targets of a primitive.
+/// Every target takes two blocks of `rows` rows in an RGB32F texture,
+/// positions first, with vertex `i` at texel `(i % width, i / width)` of its
+/// block. The texture is skipped when it would be taller than
+/// `GL_MAX_TEXTURE_SIZE`.
+#[derive(Debug)]
+struct MorphTargets {
+    texture: Option<Texture>,
+    rows: usize,
+    count: usize,
+    cpu: Option<CpuMorphTargets>,
+}
+
+/// Copy of the targets blended on the CPU, kept when the vertex shader can't
+/// blend every target: there's no texture or more than
+/// `MAX_ACTIVE_MORPH_TARGETS` targets.
+#[derive(Debug)]
+struct CpuMorphTargets {
+    /// Interleaved vertices as uploaded at import
+    base: Vec<f32>,
+    deltas: Vec<[Vec<[f32; 3]>; 2]>,
+    /// Whether the vertex buffer holds blended vertices instead of `base`
+    dirty: Cell<bool>,
+}
+
+const MORPH_TEXTURE_WIDTH: usize = 1024;
+
+/// Targets the vertex shader blends per draw, the size of the `morph_targets`
+/// and `morph_weights` arrays of `prepare.vert`. Draws with more nonzero
+/// weights are blended on the CPU.
+const MAX_ACTIVE_MORPH_TARGETS: usize = 8;
+
+/// Targets with a nonzero weight, largest weights first.
+fn active_morph_targets(weights: &[f32]) -> Vec<(usize, f32)> {
+    let mut active: Vec<(usize, f32)> = weights
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|(_, weight)| *weight != 0.0)
+        .collect();
+    active.sort_by(|a, b| b.1.abs().partial_cmp(&a.1.abs()).unwrap_or(Ordering::Equal));
+    active
+}
+
+/// Interleaved vertices of `base` displaced by the `active` targets.
+fn blend_morph_targets(
+    base: &[f32],
+    deltas: &[[Vec<[f32; 3]>; 2]],
+    active: &[(usize, f32)],
+) -> Vec<f32> {
+    let mut vertices = base.to_vec();
+    for (target, weight) in active {
+        let [positions, normals] = &deltas[*target];
+        for (i, vertex) in vertices.chunks_mut(VERTEX_SIZE).enumerate() {
+            for c in 0..3 {
+                vertex[c] += weight * positions[i][c];
+                vertex[3 + c] += weight * normals[i][c];
+            }
+        }
+    }
+    vertices
+}
+
 #[derive(Debug)]
 pub struct Mesh {
     name: String,
@@ -534,19 +963,101 @@ pub struct Mesh {
 }
 
 impl Mesh {
-    pub fn draw(&self, shader: &mut Shader) {
-        for rd in &self.data {
-            rd.draw(shader);
+    pub fn draw(&self, shader: &mut Shader, weights: &[f32]) {
+        for rd in self.data.iter().filter(|rd| !rd.is_transparent()) {
+            rd.draw(shader, weights);
         }
     }
 }
 
-#[derive(Debug, Default)]
+/// A material texture along with the UV set it samples and the
+/// `KHR_texture_transform` applied to those coordinates.
+#[derive(Debug, Clone)]
+pub struct TextureSlot {
+    pub texture: Texture,
+    pub tex_coord: u32,
+    pub offset: [f32; 2],
+    pub rotation: f32,
+    pub scale: [f32; 2],
+}
+
+impl TextureSlot {
+    pub fn new(texture: Texture) -> Self {
+        TextureSlot {
+            texture,
+            tex_coord: 0,
+            offset: [0.0, 0.0],
+            rotation: 0.0,
+            scale: [1.0, 1.0],
+        }
+    }
+
+    /// Applies a raw `KHR_texture_transform` object, see
+    /// `raw_texture_transform`.
+    fn set_raw_transform(&mut self, transform: &gltf::json::Value) {
+        let (offset, rotation, scale, tex_coord) = raw_texture_transform(transform);
+        self.offset = offset;
+        self.rotation = rotation;
+        self.scale = scale;
+        self.tex_coord = tex_coord.unwrap_or(self.tex_coord);
+    }
+
+    pub fn matrix(&self) -> na::Matrix3<f32> {
+        let (sin, cos) = self.rotation.sin_cos();
+        #[rustfmt::skip]
+        let translation = na::Matrix3::new(
+            1.0, 0.0, self.offset[0],
+            0.0, 1.0, self.offset[1],
+            0.0, 0.0, 1.0,
+        );
+        #[rustfmt::skip]
+        let rotation = na::Matrix3::new(
+            cos, sin, 0.0,
+            -sin, cos, 0.0,
+            0.0, 0.0, 1.0,
+        );
+        let scale = na::Matrix3::new_nonuniform_scaling(&na::Vector2::from(self.scale));
+        translation * rotation * scale
+    }
+}
+
+#[derive(Debug)]
 pub struct Material {
-    albedo: gl::GLuint,
+    pub albedo: Option<TextureSlot>,
+    pub normal: Option<TextureSlot>,
+    pub metallic_roughness: Option<TextureSlot>,
+    pub occlusion: Option<TextureSlot>,
+    pub emissive: Option<TextureSlot>,
     pub color: [f32; 4],
     pub metallic: f32,
     pub roughness: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub emissive_factor: [f32; 3],
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            albedo: None,
+            normal: None,
+            metallic_roughness: None,
+            occlusion: None,
+            emissive: None,
+            color: [1.0; 4],
+            metallic: 1.0,
+            roughness: 1.0,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            emissive_factor: [0.0; 3],
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            double_sided: false,
+        }
+    }
 }
 
 type SceneNode = Rc<RefCell<RealSceneNode>>;
@@ -562,244 +1073,1072 @@ impl Default for RealSceneNode {
             camera: None,
             light: None,
             mesh: None,
+            skin: None,
+            weights: Vec::new(),
         }
     }
 }
 
-impl Drop for RenderData {
-    fn drop(&mut self) {
-        if have_gl() {
-            unsafe {
-                gl::DeleteVertexArrays(1, &mut self.vao);
-                gl::DeleteBuffers(1, &mut self.vbo);
-            }
+impl RenderData {
+    pub fn new(gl: &GlContext) -> RenderData {
+        RenderData {
+            vao: VertexArray::new(gl),
+            vbo: Buffer::new(gl),
+            ebo: None,
+            skin_vbo: None,
+            morph: None,
+            mode: 0,
+            index_type: gl::GL_UNSIGNED_INT,
+            material: Rc::new(RefCell::new(Material::default())),
+            vertices: None,
+            indices: None,
+            n_elements: 0,
+            center: Point3::origin(),
         }
     }
 }
 
-impl RenderData {
-    pub fn new() -> RenderData {
-        let mut rd = RenderData {
-            vao: 0,
-            vbo: 0,
-            mode: 0,
-            material: Material::default(),
-            buffer: Vec::new(),
-            n_elements: 0,
-        };
-        unsafe {
-            gl::GenVertexArrays(1, &mut rd.vao);
-            gl::GenBuffers(1, &mut rd.vbo);
-        };
-        rd
+#[derive(Debug, Default)]
+pub struct ImportCache {
+    textures: HashMap<(usize, Option<usize>, bool), Texture>,
+    materials: HashMap<Option<usize>, Rc<RefCell<Material>>>,
+    white: Option<Texture>,
+    meshes: HashMap<usize, Rc<Mesh>>,
+    skins: HashMap<usize, Rc<Skin>>,
+    /// Raw JSON of the document, for what the gltf crate doesn't expose.
+    json: gltf::json::Value,
+}
+
+/// Internal format, pixel format and channel swizzle used to upload an
+/// image. Grayscale base colors are expanded to RGB so they read the same as
+/// in any other viewer, two channel data textures such as normal maps keep
+/// their channels.
+fn image_layout(format: image::Format, color: bool) -> (gl::GLenum, gl::GLenum, [gl::GLenum; 4]) {
+    const RGBA: [gl::GLenum; 4] = [gl::GL_RED, gl::GL_GREEN, gl::GL_BLUE, gl::GL_ALPHA];
+    const BGRA: [gl::GLenum; 4] = [gl::GL_BLUE, gl::GL_GREEN, gl::GL_RED, gl::GL_ALPHA];
+    const LUMINANCE: [gl::GLenum; 4] = [gl::GL_RED, gl::GL_RED, gl::GL_RED, gl::GL_ONE];
+    const LUMINANCE_ALPHA: [gl::GLenum; 4] = [gl::GL_RED, gl::GL_RED, gl::GL_RED, gl::GL_GREEN];
+    const RG: [gl::GLenum; 4] = [gl::GL_RED, gl::GL_GREEN, gl::GL_ONE, gl::GL_ONE];
+    let two_channels = if color { LUMINANCE_ALPHA } else { RG };
+    match format {
+        image::Format::R8 => (gl::GL_R8, gl::GL_RED, LUMINANCE),
+        image::Format::R8G8 => (gl::GL_RG8, gl::GL_RG, two_channels),
+        image::Format::R8G8B8 => (gl::GL_RGB8, gl::GL_RGB, RGBA),
+        image::Format::R8G8B8A8 => (gl::GL_RGBA8, gl::GL_RGBA, RGBA),
+        image::Format::B8G8R8 => (gl::GL_RGB8, gl::GL_RGB, BGRA),
+        image::Format::B8G8R8A8 => (gl::GL_RGBA8, gl::GL_RGBA, BGRA),
+        image::Format::R16 => (gl::GL_R16F, gl::GL_RED, LUMINANCE),
+        image::Format::R16G16 => (gl::GL_RG16F, gl::GL_RG, two_channels),
+        image::Format::R16G16B16 => (gl::GL_RGB16F, gl::GL_RGB, RGBA),
+        image::Format::R16G16B16A16 => (gl::GL_RGBA16F, gl::GL_RGBA, RGBA),
     }
 }
 
-pub fn create_mesh(
-    mesh: gltf::Mesh,
-    buffers: &[gltf::buffer::Data],
+/// Uploads the image of `texture`, `color` is set for base color and
+/// emissive textures.
+fn load_texture(
+    gl: &GlContext,
+    texture: gltf::Texture,
+    color: bool,
     images: &[image::Data],
-) -> Mesh {
-    let name = String::from(mesh.name().unwrap_or("NULL"));
-    let mut data = Vec::new();
-    for primitive in mesh.primitives() {
-        let mut rd = RenderData::new();
-        rd.mode = primitive.mode().as_gl_enum();
-        let model = primitive.material().pbr_metallic_roughness();
-        let mut material = Material {
-            albedo: 0,
-            color: model.base_color_factor(),
-            metallic: model.metallic_factor(),
-            roughness: model.roughness_factor(),
-        };
-        unsafe {
-            gl::GenTextures(1, &mut material.albedo);
-            gl::BindTexture(gl::GL_TEXTURE_2D, material.albedo);
-            gl::PixelStorei(gl::GL_PACK_ALIGNMENT, 1);
+    cache: &mut ImportCache,
+) -> Result<Texture> {
+    let index = texture.source().index();
+    let sampler = texture.sampler();
+    let key = (index, sampler.index(), color);
+    if let Some(texture) = cache.textures.get(&key) {
+        return Ok(texture.clone());
+    }
+    let data = &images[index];
+    let (internal_format, format, swizzle) = image_layout(data.format, color);
+    // GLES has no normalized 16-bit formats, those images are widened to
+    // floats and stored as half floats. Half floats aren't color-renderable
+    // on GLES 3.0 so they can't be mipmapped either.
+    let half_float = matches!(
+        data.format,
+        image::Format::R16
+            | image::Format::R16G16
+            | image::Format::R16G16B16
+            | image::Format::R16G16B16A16
+    );
+    let wide: Vec<f32>;
+    let (type_, pixels) = if half_float {
+        wide = data
+            .pixels
+            .chunks_exact(2)
+            .map(|c| u16::from_ne_bytes([c[0], c[1]]) as f32 / 65535.0)
+            .collect();
+        (gl::GL_FLOAT, wide.as_ptr() as *const c_void)
+    } else {
+        (gl::GL_UNSIGNED_BYTE, data.pixels.as_ptr() as *const c_void)
+    };
+    let min_filter = sampler
+        .min_filter()
+        .unwrap_or(gltf::texture::MinFilter::LinearMipmapLinear);
+    let min_filter = match min_filter {
+        gltf::texture::MinFilter::NearestMipmapNearest
+        | gltf::texture::MinFilter::NearestMipmapLinear
+            if half_float =>
+        {
+            gltf::texture::MinFilter::Nearest
         }
-        if let Some(texture) = model.base_color_texture() {
-            if texture.tex_coord() != 0 {
-                error!("Assuming tex_coord 0, but it's {}", texture.tex_coord());
-            }
-            let texture = texture.texture();
-            assert_eq!(texture.index(), texture.source().index());
-            let index = texture.index();
-            let format = match images[index].format {
-                image::Format::R8G8B8 => gl::GL_RGB,
-                image::Format::R8G8B8A8 => gl::GL_RGBA,
-                _ => unimplemented!(),
-            };
-            let sampler = texture.sampler();
-            unsafe {
-                gl::TexImage2D(
-                    gl::GL_TEXTURE_2D,
-                    0,
-                    format as i32,
-                    images[index].width as i32,
-                    images[index].height as i32,
-                    0,
-                    format,
-                    gl::GL_UNSIGNED_BYTE,
-                    images[index].pixels.as_ptr() as *const c_void,
-                );
-                gl::GenerateMipmap(gl::GL_TEXTURE_2D);
-                gl::TexParameteri(
-                    gl::GL_TEXTURE_2D,
-                    gl::GL_TEXTURE_MIN_FILTER,
-                    sampler
-                        .min_filter()
-                        .unwrap_or(gltf::texture::MinFilter::LinearMipmapLinear)
-                        .as_gl_enum() as i32,
-                );
-                gl::TexParameteri(
-                    gl::GL_TEXTURE_2D,
-                    gl::GL_TEXTURE_MAG_FILTER,
-                    sampler
-                        .mag_filter()
-                        .unwrap_or(gltf::texture::MagFilter::Linear)
-                        .as_gl_enum() as i32,
-                );
-                gl::TexParameteri(
-                    gl::GL_TEXTURE_2D,
-                    gl::GL_TEXTURE_WRAP_S,
-                    sampler.wrap_s().as_gl_enum() as i32,
-                );
-                gl::TexParameteri(
-                    gl::GL_TEXTURE_2D,
-                    gl::GL_TEXTURE_WRAP_T,
-                    sampler.wrap_s().as_gl_enum() as i32,
-                );
-            }
+        gltf::texture::MinFilter::LinearMipmapNearest
+        | gltf::texture::MinFilter::LinearMipmapLinear
+            if half_float =>
+        {
+            gltf::texture::MinFilter::Linear
+        }
+        filter => filter,
+    };
+    let handle = Texture::new(gl);
+    unsafe {
+        gl::BindTexture(gl::GL_TEXTURE_2D, handle.id());
+        gl::PixelStorei(gl::GL_UNPACK_ALIGNMENT, 1);
+        gl::TexImage2D(
+            gl::GL_TEXTURE_2D,
+            0,
+            internal_format as i32,
+            data.width as i32,
+            data.height as i32,
+            0,
+            format,
+            type_,
+            pixels,
+        );
+        let swizzle_names = [
+            gl::GL_TEXTURE_SWIZZLE_R,
+            gl::GL_TEXTURE_SWIZZLE_G,
+            gl::GL_TEXTURE_SWIZZLE_B,
+            gl::GL_TEXTURE_SWIZZLE_A,
+        ];
+        for (name, source) in swizzle_names.iter().zip(swizzle.iter()) {
+            gl::TexParameteri(gl::GL_TEXTURE_2D, *name, *source as i32);
+        }
+        if half_float {
+            handle.set_size(texture_size(
+                internal_format,
+                data.width as usize,
+                data.height as usize,
+            ));
         } else {
-            let white: [u8; 4] = [255; 4];
-            unsafe {
-                gl::TexImage2D(
-                    gl::GL_TEXTURE_2D,
-                    0,
-                    gl::GL_RGBA as i32,
-                    1,
-                    1,
-                    0,
-                    gl::GL_RGBA,
-                    gl::GL_UNSIGNED_BYTE,
-                    white.as_ptr() as *const c_void,
-                );
-                gl::TexParameteri(
-                    gl::GL_TEXTURE_2D,
-                    gl::GL_TEXTURE_MIN_FILTER,
-                    gl::GL_NEAREST as i32,
-                );
-                gl::TexParameteri(
-                    gl::GL_TEXTURE_2D,
-                    gl::GL_TEXTURE_MAG_FILTER,
-                    gl::GL_NEAREST as i32,
-                );
-                gl::TexParameteri(
-                    gl::GL_TEXTURE_2D,
-                    gl::GL_TEXTURE_WRAP_S,
-                    gl::GL_CLAMP_TO_EDGE as i32,
-                );
-                gl::TexParameteri(
-                    gl::GL_TEXTURE_2D,
-                    gl::GL_TEXTURE_WRAP_T,
-                    gl::GL_CLAMP_TO_EDGE as i32,
-                );
+            gl::GenerateMipmap(gl::GL_TEXTURE_2D);
+            handle.set_size(mipmapped_texture_size(
+                internal_format,
+                data.width as usize,
+                data.height as usize,
+            ));
+        }
+        gl::TexParameteri(
+            gl::GL_TEXTURE_2D,
+            gl::GL_TEXTURE_MIN_FILTER,
+            min_filter.as_gl_enum() as i32,
+        );
+        gl::TexParameteri(
+            gl::GL_TEXTURE_2D,
+            gl::GL_TEXTURE_MAG_FILTER,
+            sampler
+                .mag_filter()
+                .unwrap_or(gltf::texture::MagFilter::Linear)
+                .as_gl_enum() as i32,
+        );
+        gl::TexParameteri(
+            gl::GL_TEXTURE_2D,
+            gl::GL_TEXTURE_WRAP_S,
+            sampler.wrap_s().as_gl_enum() as i32,
+        );
+        gl::TexParameteri(
+            gl::GL_TEXTURE_2D,
+            gl::GL_TEXTURE_WRAP_T,
+            sampler.wrap_t().as_gl_enum() as i32,
+        );
+        gl::BindTexture(gl::GL_TEXTURE_2D, 0);
+    }
+    cache.textures.insert(key, handle.clone());
+    Ok(handle)
+}
+
+fn load_skin(
+    gl: &GlContext,
+    skin: gltf::Skin,
+    buffers: &[gltf::buffer::Data],
+    cache: &mut ImportCache,
+) -> Rc<Skin> {
+    if let Some(skin) = cache.skins.get(&skin.index()) {
+        return skin.clone();
+    }
+    let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+    let reader = skin.reader(|x| Some(&buffers[x.index()]));
+    let inverse_bind_matrices = match reader.read_inverse_bind_matrices() {
+        Some(matrices) => matrices.map(na::Matrix4::from).collect(),
+        None => vec![na::Matrix4::identity(); joints.len()],
+    };
+    let texture = Texture::new(gl);
+    unsafe {
+        gl::BindTexture(gl::GL_TEXTURE_2D, texture.id());
+        gl::TexParameteri(
+            gl::GL_TEXTURE_2D,
+            gl::GL_TEXTURE_MIN_FILTER,
+            gl::GL_NEAREST as i32,
+        );
+        gl::TexParameteri(
+            gl::GL_TEXTURE_2D,
+            gl::GL_TEXTURE_MAG_FILTER,
+            gl::GL_NEAREST as i32,
+        );
+        gl::BindTexture(gl::GL_TEXTURE_2D, 0);
+    }
+    let result = Rc::new(Skin {
+        name: String::from(skin.name().unwrap_or("NULL")),
+        joints,
+        inverse_bind_matrices,
+        texture,
+    });
+    result.update(&HashMap::new());
+    cache.skins.insert(skin.index(), result.clone());
+    result
+}
+
+fn create_morph_targets(
+    gl: &GlContext,
+    targets: Vec<[Vec<[f32; 3]>; 2]>,
+    vertices: &[f32],
+) -> MorphTargets {
+    let count = targets.len();
+    let n_vertices = vertices.len() / VERTEX_SIZE;
+    let width = n_vertices.clamp(1, MORPH_TEXTURE_WIDTH);
+    let rows = n_vertices.div_ceil(width);
+    let height = rows * 2 * count;
+    let mut max_size: gl::GLint = 0;
+    unsafe {
+        gl::GetIntegerv(gl::GL_MAX_TEXTURE_SIZE, &mut max_size);
+    }
+    let texture = if height <= max_size as usize {
+        Some(morph_texture(gl, &targets, width, rows))
+    } else {
+        warn!(
+            "{} morph targets need a {}x{} texture, over GL_MAX_TEXTURE_SIZE {}, blending them on the CPU",
+            count,
+            width,
+            height,
+            max_size
+        );
+        None
+    };
+    let cpu = if texture.is_none() || count > MAX_ACTIVE_MORPH_TARGETS {
+        Some(CpuMorphTargets {
+            base: vertices.to_vec(),
+            deltas: targets,
+            dirty: Cell::new(false),
+        })
+    } else {
+        None
+    };
+    MorphTargets {
+        texture,
+        rows,
+        count,
+        cpu,
+    }
+}
+
+fn morph_texture(
+    gl: &GlContext,
+    targets: &[[Vec<[f32; 3]>; 2]],
+    width: usize,
+    rows: usize,
+) -> Texture {
+    let mut texels = vec![0.0f32; width * rows * 2 * targets.len() * 3];
+    for (i, block) in targets.iter().flat_map(|target| target.iter()).enumerate() {
+        let start = i * width * rows * 3;
+        for (j, delta) in block.iter().enumerate() {
+            texels[start + j * 3..start + j * 3 + 3].copy_from_slice(delta);
+        }
+    }
+    let texture = Texture::new(gl);
+    let height = rows * 2 * targets.len();
+    unsafe {
+        gl::BindTexture(gl::GL_TEXTURE_2D, texture.id());
+        gl::TexParameteri(
+            gl::GL_TEXTURE_2D,
+            gl::GL_TEXTURE_MIN_FILTER,
+            gl::GL_NEAREST as i32,
+        );
+        gl::TexParameteri(
+            gl::GL_TEXTURE_2D,
+            gl::GL_TEXTURE_MAG_FILTER,
+            gl::GL_NEAREST as i32,
+        );
+        gl::PixelStorei(gl::GL_UNPACK_ALIGNMENT, 1);
+        gl::TexImage2D(
+            gl::GL_TEXTURE_2D,
+            0,
+            gl::GL_RGB32F as i32,
+            width as i32,
+            height as i32,
+            0,
+            gl::GL_RGB,
+            gl::GL_FLOAT,
+            texels.as_ptr() as *const c_void,
+        );
+        gl::BindTexture(gl::GL_TEXTURE_2D, 0);
+    }
+    texture.set_size(texture_size(gl::GL_RGB32F, width, height));
+    texture
+}
+
+fn white_texture(gl: &GlContext, cache: &mut ImportCache) -> Texture {
+    if let Some(texture) = &cache.white {
+        return texture.clone();
+    }
+    let handle = Texture::new(gl);
+    let white: [u8; 4] = [255; 4];
+    unsafe {
+        gl::BindTexture(gl::GL_TEXTURE_2D, handle.id());
+        gl::TexImage2D(
+            gl::GL_TEXTURE_2D,
+            0,
+            gl::GL_RGBA as i32,
+            1,
+            1,
+            0,
+            gl::GL_RGBA,
+            gl::GL_UNSIGNED_BYTE,
+            white.as_ptr() as *const c_void,
+        );
+        handle.set_size(texture_size(gl::GL_RGBA, 1, 1));
+        gl::TexParameteri(
+            gl::GL_TEXTURE_2D,
+            gl::GL_TEXTURE_MIN_FILTER,
+            gl::GL_NEAREST as i32,
+        );
+        gl::TexParameteri(
+            gl::GL_TEXTURE_2D,
+            gl::GL_TEXTURE_MAG_FILTER,
+            gl::GL_NEAREST as i32,
+        );
+        gl::TexParameteri(
+            gl::GL_TEXTURE_2D,
+            gl::GL_TEXTURE_WRAP_S,
+            gl::GL_CLAMP_TO_EDGE as i32,
+        );
+        gl::TexParameteri(
+            gl::GL_TEXTURE_2D,
+            gl::GL_TEXTURE_WRAP_T,
+            gl::GL_CLAMP_TO_EDGE as i32,
+        );
+        gl::BindTexture(gl::GL_TEXTURE_2D, 0);
+    }
+    cache.white = Some(handle.clone());
+    handle
+}
+
+fn load_material(
+    gl: &GlContext,
+    material: gltf::Material,
+    images: &[image::Data],
+    cache: &mut ImportCache,
+) -> Result<Rc<RefCell<Material>>> {
+    if let Some(material) = cache.materials.get(&material.index()) {
+        return Ok(material.clone());
+    }
+    let model = material.pbr_metallic_roughness();
+    // The gltf crate doesn't expose KHR_texture_transform on normal and
+    // occlusion texture infos, it's read back from the raw JSON
+    let raw_transform = |slot: &str| {
+        let pointer = format!(
+            "/materials/{}/{}/extensions/KHR_texture_transform",
+            material.index()?,
+            slot
+        );
+        cache.json.pointer(&pointer).cloned()
+    };
+    let normal_transform = raw_transform("normalTexture");
+    let occlusion_transform = raw_transform("occlusionTexture");
+    let mut load = |info: gltf::texture::Info, color: bool| -> Result<TextureSlot> {
+        let mut slot = TextureSlot::new(load_texture(gl, info.texture(), color, images, cache)?);
+        slot.tex_coord = info.tex_coord();
+        if let Some(transform) = info.texture_transform() {
+            slot.tex_coord = transform.tex_coord().unwrap_or(slot.tex_coord);
+            slot.offset = transform.offset();
+            slot.rotation = transform.rotation();
+            slot.scale = transform.scale();
+        }
+        Ok(slot)
+    };
+    let albedo = model
+        .base_color_texture()
+        .map(|info| load(info, true))
+        .transpose()?;
+    let metallic_roughness = model
+        .metallic_roughness_texture()
+        .map(|info| load(info, false))
+        .transpose()?;
+    let emissive = material
+        .emissive_texture()
+        .map(|info| load(info, true))
+        .transpose()?;
+    let (normal, normal_scale) = match material.normal_texture() {
+        Some(texture) => {
+            let mut slot =
+                TextureSlot::new(load_texture(gl, texture.texture(), false, images, cache)?);
+            slot.tex_coord = texture.tex_coord();
+            if let Some(transform) = &normal_transform {
+                slot.set_raw_transform(transform);
             }
+            (Some(slot), texture.scale())
         }
-        unsafe {
-            gl::BindTexture(gl::GL_TEXTURE_2D, 0);
+        None => (None, 1.0),
+    };
+    let (occlusion, occlusion_strength) = match material.occlusion_texture() {
+        Some(texture) => {
+            let mut slot =
+                TextureSlot::new(load_texture(gl, texture.texture(), false, images, cache)?);
+            slot.tex_coord = texture.tex_coord();
+            if let Some(transform) = &occlusion_transform {
+                slot.set_raw_transform(transform);
+            }
+            (Some(slot), texture.strength())
         }
-        rd.material = material;
-        let reader = primitive.reader(|x| {
-            assert!(match x.source() {
-                Source::Bin => true,
-                _ => false,
-            });
-            Some(&buffers[x.index()])
-        });
-        let pos: Vec<_> = reader.read_positions().expect("No positions!").collect();
-        let norm: Vec<_> = reader.read_normals().expect("No normals!").collect();
-        let texcoord: Vec<_> = if let Some(tex) = reader.read_tex_coords(0) {
-            tex.into_f32().collect()
-        } else {
-            vec![[0.0, 0.0]; pos.len()]
-        };
-        let ind: Vec<_> = match reader.read_indices() {
-            Some(x) => x.into_u32().map(|y| y as usize).collect(),
-            None => (0..pos.len()).collect(),
-        };
-        for i in ind {
-            for j in 0..3 {
-                rd.buffer.push(pos[i][j]);
+        None => (None, 1.0),
+    };
+    // Missing textures are replaced by a white texel so the factors alone
+    // determine the result
+    let mut white = || Some(TextureSlot::new(white_texture(gl, cache)));
+    let result = Rc::new(RefCell::new(Material {
+        albedo: albedo.or_else(&mut white),
+        normal,
+        metallic_roughness: metallic_roughness.or_else(&mut white),
+        occlusion: occlusion.or_else(&mut white),
+        emissive: emissive.or_else(&mut white),
+        color: model.base_color_factor(),
+        metallic: model.metallic_factor(),
+        roughness: model.roughness_factor(),
+        normal_scale,
+        occlusion_strength,
+        emissive_factor: material.emissive_factor(),
+        alpha_mode: material.alpha_mode(),
+        alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5),
+        double_sided: material.double_sided(),
+    }));
+    cache.materials.insert(material.index(), result.clone());
+    Ok(result)
+}
+
+/// Components of each vertex attribute, indexed by shader location:
+/// position, normal, texcoord 0, tangent and texcoord 1.
+const VERTEX_LAYOUT: [usize; 5] = [3, 3, 2, 4, 2];
+const VERTEX_SIZE: usize = 14;
+
+/// Joint indices and weights of every vertex.
+type SkinAttributes = (Vec<[u16; 4]>, Vec<[f32; 4]>);
+
+/// Per-vertex tangents for an indexed or plain triangle list, in the layout
+/// glTF expects: `xyz` is orthogonal to the normal and `w` is the handedness
+/// of the bitangent, `cross(normal, tangent) * w`.
+///
+/// Triangle tangents are summed on shared vertices weighted by area
+/// (Lengyel's method). Vertices aren't split at UV seams and corners aren't
+/// weighted by angle, so this only approximates MikkTSpace: normal maps
+/// baked by other tools can show seams unless the asset carries tangents.
+fn generate_tangents(
+    pos: &[[f32; 3]],
+    norm: &[[f32; 3]],
+    texcoord: &[[f32; 2]],
+    indices: Option<&[u32]>,
+) -> Vec<[f32; 4]> {
+    let mut tangents = vec![na::Vector3::<f32>::zeros(); pos.len()];
+    let mut bitangents = vec![na::Vector3::<f32>::zeros(); pos.len()];
+    let n_triangles = indices.map_or(pos.len(), |i| i.len()) / 3;
+    for triangle in 0..n_triangles {
+        let mut v = [0; 3];
+        for (k, v) in v.iter_mut().enumerate() {
+            let i = triangle * 3 + k;
+            *v = indices.map_or(i, |indices| indices[i] as usize);
+        }
+        let p0 = na::Vector3::from(pos[v[0]]);
+        let e1 = na::Vector3::from(pos[v[1]]) - p0;
+        let e2 = na::Vector3::from(pos[v[2]]) - p0;
+        let t0 = na::Vector2::from(texcoord[v[0]]);
+        let d1 = na::Vector2::from(texcoord[v[1]]) - t0;
+        let d2 = na::Vector2::from(texcoord[v[2]]) - t0;
+        let det = d1.x * d2.y - d2.x * d1.y;
+        if det == 0.0 {
+            continue;
+        }
+        // Weighting by the triangle area comes for free from the unnormalized
+        // edge vectors
+        let sign = det.signum();
+        let tangent = (e1 * d2.y - e2 * d1.y) * sign;
+        let bitangent = (e2 * d1.x - e1 * d2.x) * sign;
+        for &i in v.iter() {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+    (0..pos.len())
+        .map(|i| {
+            let n = na::Vector3::from(norm[i]).normalize();
+            // Gram-Schmidt against the normal, pick any perpendicular axis if
+            // the texture mapping is degenerate
+            let mut t = tangents[i] - n * n.dot(&tangents[i]);
+            if t.norm_squared() <= f32::EPSILON {
+                let axis = if n.x.abs() < 0.9 {
+                    na::Vector3::x()
+                } else {
+                    na::Vector3::y()
+                };
+                t = axis - n * n.dot(&axis);
             }
-            for j in 0..3 {
-                rd.buffer.push(norm[i][j]);
+            let t = t.normalize();
+            let w = if n.cross(&t).dot(&bitangents[i]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            [t.x, t.y, t.z, w]
+        })
+        .collect()
+}
+
+pub fn create_mesh(
+    gl: &GlContext,
+    mesh: gltf::Mesh,
+    buffers: &[gltf::buffer::Data],
+    images: &[image::Data],
+    cache: &mut ImportCache,
+    options: &ImportOptions,
+) -> Result<Mesh> {
+    let name = String::from(mesh.name().unwrap_or("NULL"));
+    let mut data = Vec::new();
+    for primitive in mesh.primitives() {
+        let mut rd = RenderData::new(gl);
+        rd.mode = primitive.mode().as_gl_enum();
+        rd.material = load_material(gl, primitive.material(), images, cache)?;
+        let bounds = primitive.bounding_box();
+        rd.center =
+            Point3::from((na::Vector3::from(bounds.min) + na::Vector3::from(bounds.max)) / 2.0);
+        let reader = primitive.reader(|x| Some(&buffers[x.index()]));
+        let mut pos: Vec<_> = reader
+            .read_positions()
+            .ok_or_else(|| Error::MissingAttribute {
+                mesh: name.clone(),
+                attribute: "POSITION",
+            })?
+            .collect();
+        let mut norm: Vec<_> = reader
+            .read_normals()
+            .ok_or_else(|| Error::MissingAttribute {
+                mesh: name.clone(),
+                attribute: "NORMAL",
+            })?
+            .collect();
+        let mut texcoord: Vec<Vec<_>> = (0..2)
+            .map(|set| match reader.read_tex_coords(set) {
+                Some(tex) => tex.into_f32().collect(),
+                None => vec![[0.0, 0.0]; pos.len()],
+            })
+            .collect();
+        let mut indices: Option<Vec<u32>> = reader.read_indices().map(|x| x.into_u32().collect());
+        let mut skin: Option<SkinAttributes> = match (reader.read_joints(0), reader.read_weights(0))
+        {
+            (Some(joints), Some(weights)) => {
+                Some((joints.into_u16().collect(), weights.into_f32().collect()))
             }
-            for j in 0..2 {
-                rd.buffer.push(texcoord[i][j]);
+            _ => None,
+        };
+        // Position and normal displacements of each target, missing ones are
+        // zero
+        let mut morph: Vec<[Vec<[f32; 3]>; 2]> = reader
+            .read_morph_targets()
+            .map(|(positions, normals, _)| {
+                [
+                    positions.map_or_else(|| vec![[0.0; 3]; pos.len()], |p| p.collect()),
+                    normals.map_or_else(|| vec![[0.0; 3]; pos.len()], |n| n.collect()),
+                ]
+            })
+            .collect();
+        let mut tangent: Vec<_> = if let Some(tangent) = reader.read_tangents() {
+            tangent.collect()
+        } else if let Some(normal) = primitive.material().normal_texture() {
+            if rd.mode == gl::GL_TRIANGLES {
+                let set = (normal.tex_coord() as usize).min(1);
+                generate_tangents(&pos, &norm, &texcoord[set], indices.as_deref())
+            } else {
+                warn!(
+                    "Mesh \"{}\": cannot generate tangents for primitive mode {}",
+                    name, rd.mode
+                );
+                vec![[1.0, 0.0, 0.0, 1.0]; pos.len()]
             }
-            rd.n_elements += 1;
+        } else {
+            vec![[1.0, 0.0, 0.0, 1.0]; pos.len()]
+        };
+        if options.deindex {
+            if let Some(order) = indices.take() {
+                pos = deindex(&pos, &order);
+                norm = deindex(&norm, &order);
+                for set in texcoord.iter_mut() {
+                    *set = deindex(set, &order);
+                }
+                tangent = deindex(&tangent, &order);
+                if let Some((joints, weights)) = &mut skin {
+                    *joints = deindex(joints, &order);
+                    *weights = deindex(weights, &order);
+                }
+                for target in morph.iter_mut() {
+                    for block in target.iter_mut() {
+                        *block = deindex(block, &order);
+                    }
+                }
+            }
+        }
+        let mut vertices = Vec::with_capacity(pos.len() * VERTEX_SIZE);
+        for i in 0..pos.len() {
+            vertices.extend_from_slice(&pos[i]);
+            vertices.extend_from_slice(&norm[i]);
+            vertices.extend_from_slice(&texcoord[0][i]);
+            vertices.extend_from_slice(&tangent[i]);
+            vertices.extend_from_slice(&texcoord[1][i]);
+        }
+        if !morph.is_empty() {
+            rd.morph = Some(create_morph_targets(gl, morph, &vertices));
         }
         unsafe {
-            gl::BindVertexArray(rd.vao);
-            gl::BindBuffer(gl::GL_ARRAY_BUFFER, rd.vbo);
+            gl::BindVertexArray(rd.vao.id());
+            gl::BindBuffer(gl::GL_ARRAY_BUFFER, rd.vbo.id());
             gl::BufferData(
                 gl::GL_ARRAY_BUFFER,
-                (rd.buffer.len() * size_of::<gl::GLfloat>()) as isize,
-                rd.buffer.as_ptr() as *const c_void,
+                (vertices.len() * size_of::<gl::GLfloat>()) as isize,
+                vertices.as_ptr() as *const c_void,
                 gl::GL_STATIC_DRAW,
             );
-            gl::VertexAttribPointer(
-                0,
-                3,
-                gl::GL_FLOAT,
-                gl::GL_FALSE,
-                8 * size_of::<gl::GLfloat>() as i32,
-                null(),
-            );
-            gl::VertexAttribPointer(
-                1,
-                3,
-                gl::GL_FLOAT,
-                gl::GL_FALSE,
-                8 * size_of::<gl::GLfloat>() as i32,
-                null::<c_void>().offset(3 * size_of::<gl::GLfloat>() as isize),
-            );
-            gl::VertexAttribPointer(
-                2,
-                2,
-                gl::GL_FLOAT,
-                gl::GL_FALSE,
-                8 * size_of::<gl::GLfloat>() as i32,
-                null::<c_void>().offset(6 * size_of::<gl::GLfloat>() as isize),
-            );
-            gl::EnableVertexAttribArray(0);
-            gl::EnableVertexAttribArray(1);
-            gl::EnableVertexAttribArray(2);
-            gl::BindBuffer(gl::GL_ARRAY_BUFFER, 0);
+            rd.vbo.set_size(vertices.len() * size_of::<gl::GLfloat>());
+            if let Some(indices) = &indices {
+                let ebo = Buffer::new(gl);
+                gl::BindBuffer(gl::GL_ELEMENT_ARRAY_BUFFER, ebo.id());
+                // The element buffer binding is part of the VAO state
+                if pos.len() <= u16::MAX as usize + 1 {
+                    let short: Vec<u16> = indices.iter().map(|&i| i as u16).collect();
+                    gl::BufferData(
+                        gl::GL_ELEMENT_ARRAY_BUFFER,
+                        (short.len() * size_of::<u16>()) as isize,
+                        short.as_ptr() as *const c_void,
+                        gl::GL_STATIC_DRAW,
+                    );
+                    ebo.set_size(short.len() * size_of::<u16>());
+                    rd.index_type = gl::GL_UNSIGNED_SHORT;
+                } else {
+                    gl::BufferData(
+                        gl::GL_ELEMENT_ARRAY_BUFFER,
+                        (indices.len() * size_of::<u32>()) as isize,
+                        indices.as_ptr() as *const c_void,
+                        gl::GL_STATIC_DRAW,
+                    );
+                    ebo.set_size(indices.len() * size_of::<u32>());
+                    rd.index_type = gl::GL_UNSIGNED_INT;
+                }
+                rd.ebo = Some(ebo);
+            }
+            let mut offset = 0;
+            for (location, components) in VERTEX_LAYOUT.iter().enumerate() {
+                gl::VertexAttribPointer(
+                    location as gl::GLuint,
+                    *components as i32,
+                    gl::GL_FLOAT,
+                    gl::GL_FALSE,
+                    (VERTEX_SIZE * size_of::<gl::GLfloat>()) as i32,
+                    null::<c_void>().add(offset * size_of::<gl::GLfloat>()),
+                );
+                gl::EnableVertexAttribArray(location as gl::GLuint);
+                offset += components;
+            }
+            // Joints and weights of skinned primitives live in a buffer of
+            // their own, joints first
+            if let Some((joints, weights)) = &skin {
+                let skin_vbo = Buffer::new(gl);
+                let joints_size = joints.len() * size_of::<[u16; 4]>();
+                let weights_size = weights.len() * size_of::<[f32; 4]>();
+                gl::BindBuffer(gl::GL_ARRAY_BUFFER, skin_vbo.id());
+                gl::BufferData(
+                    gl::GL_ARRAY_BUFFER,
+                    (joints_size + weights_size) as isize,
+                    null(),
+                    gl::GL_STATIC_DRAW,
+                );
+                gl::BufferSubData(
+                    gl::GL_ARRAY_BUFFER,
+                    0,
+                    joints_size as isize,
+                    joints.as_ptr() as *const c_void,
+                );
+                gl::BufferSubData(
+                    gl::GL_ARRAY_BUFFER,
+                    joints_size as isize,
+                    weights_size as isize,
+                    weights.as_ptr() as *const c_void,
+                );
+                skin_vbo.set_size(joints_size + weights_size);
+                let location = VERTEX_LAYOUT.len() as gl::GLuint;
+                gl::VertexAttribIPointer(location, 4, gl::GL_UNSIGNED_SHORT, 0, null());
+                gl::EnableVertexAttribArray(location);
+                gl::VertexAttribPointer(
+                    location + 1,
+                    4,
+                    gl::GL_FLOAT,
+                    gl::GL_FALSE,
+                    0,
+                    null::<c_void>().add(joints_size),
+                );
+                gl::EnableVertexAttribArray(location + 1);
+                rd.skin_vbo = Some(skin_vbo);
+            }
             gl::BindVertexArray(0);
+            gl::BindBuffer(gl::GL_ARRAY_BUFFER, 0);
+        }
+        rd.n_elements = indices.as_ref().map_or(pos.len(), |i| i.len()) as i32;
+        if options.keep_vertex_data {
+            rd.vertices = Some(vertices);
+            rd.indices = indices;
         }
         data.push(rd);
     }
-    Mesh { name, data }
+    Ok(Mesh { name, data })
+}
+
+/// One copy of `values` per index, in index order.
+fn deindex<T: Copy>(values: &[T], indices: &[u32]) -> Vec<T> {
+    indices.iter().map(|&i| values[i as usize]).collect()
+}
+
+/// Which of the document's scenes is shown after importing it.
+#[derive(Debug, Clone, Default)]
+pub enum SceneSelector {
+    /// The scene the document marks as default, or the first one.
+    #[default]
+    Default,
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    pub keep_vertex_data: bool,
+    /// Expands indexed primitives to one vertex per index and draws them
+    /// without an element buffer, to measure what indexing saves.
+    pub deindex: bool,
+    pub scene: SceneSelector,
+    /// Leaves every animation stopped instead of looping from the start.
+    pub stop_animations: bool,
+}
+
+pub fn import_scene(gl: &GlContext, asset: &[u8], width: u32, height: u32) -> Scene {
+    try_import_scene(gl, asset, width, height).unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_import_scene(gl: &GlContext, asset: &[u8], width: u32, height: u32) -> Result<Scene> {
+    try_import_scene_with_options(gl, asset, width, height, &ImportOptions::default())
+}
+
+pub fn import_scene_with_options(
+    gl: &GlContext,
+    asset: &[u8],
+    width: u32,
+    height: u32,
+    options: &ImportOptions,
+) -> Scene {
+    try_import_scene_with_options(gl, asset, width, height, options)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_import_scene_with_options(
+    gl: &GlContext,
+    asset: &[u8],
+    width: u32,
+    height: u32,
+    options: &ImportOptions,
+) -> Result<Scene> {
+    let (document, buffers, images) = import_gltf(asset, None)?;
+    load_scene(
+        gl,
+        document,
+        raw_json(asset),
+        &buffers,
+        &images,
+        width,
+        height,
+        options,
+    )
+}
+
+pub fn import_scene_from_path<P: AsRef<Path>>(
+    gl: &GlContext,
+    path: P,
+    width: u32,
+    height: u32,
+) -> Scene {
+    try_import_scene_from_path(gl, path, width, height).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Imports a `.gltf` or `.glb` file, resolving relative URIs against the
+/// directory containing it.
+pub fn try_import_scene_from_path<P: AsRef<Path>>(
+    gl: &GlContext,
+    path: P,
+    width: u32,
+    height: u32,
+) -> Result<Scene> {
+    try_import_scene_from_path_with_options(gl, path, width, height, &ImportOptions::default())
+}
+
+pub fn import_scene_from_path_with_options<P: AsRef<Path>>(
+    gl: &GlContext,
+    path: P,
+    width: u32,
+    height: u32,
+    options: &ImportOptions,
+) -> Scene {
+    try_import_scene_from_path_with_options(gl, path, width, height, options)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_import_scene_from_path_with_options<P: AsRef<Path>>(
+    gl: &GlContext,
+    path: P,
+    width: u32,
+    height: u32,
+    options: &ImportOptions,
+) -> Result<Scene> {
+    let path = path.as_ref();
+    let asset = fs::read(path).map_err(gltf::Error::Io)?;
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    let (document, buffers, images) = import_gltf(&asset, Some(base))?;
+    load_scene(
+        gl,
+        document,
+        raw_json(&asset),
+        &buffers,
+        &images,
+        width,
+        height,
+        options,
+    )
+}
+
+/// Offset, rotation, scale and UV set override of a `KHR_texture_transform`
+/// object, missing fields take their defaults.
+fn raw_texture_transform(transform: &gltf::json::Value) -> ([f32; 2], f32, [f32; 2], Option<u32>) {
+    let pair = |key: &str, default: [f32; 2]| {
+        transform
+            .get(key)
+            .and_then(|v| Some([v.get(0)?.as_f64()? as f32, v.get(1)?.as_f64()? as f32]))
+            .unwrap_or(default)
+    };
+    let rotation = transform.get("rotation").and_then(|v| v.as_f64());
+    let tex_coord = transform.get("texCoord").and_then(|v| v.as_u64());
+    (
+        pair("offset", [0.0, 0.0]),
+        rotation.unwrap_or(0.0) as f32,
+        pair("scale", [1.0, 1.0]),
+        tex_coord.map(|t| t as u32),
+    )
+}
+
+/// JSON of a `.gltf` or `.glb` asset, `null` if it can't be parsed.
+fn raw_json(asset: &[u8]) -> gltf::json::Value {
+    let json = if asset.starts_with(b"glTF") {
+        match gltf::Glb::from_slice(asset) {
+            Ok(glb) => glb.json,
+            Err(_) => return gltf::json::Value::Null,
+        }
+    } else {
+        Cow::Borrowed(asset)
+    };
+    gltf::json::deserialize::from_slice(&json).unwrap_or_default()
+}
+
+/// Scheme of an URI, `None` for relative references and Windows paths like
+/// `C:\textures\wood.png`.
+fn uri_scheme(uri: &str) -> Option<&str> {
+    let scheme = &uri[..uri.find(':')?];
+    let mut chars = scheme.chars();
+    let first = chars.next()?;
+    let valid = first.is_ascii_alphabetic()
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+    // A single letter is a drive
+    if valid && scheme.len() > 1 {
+        Some(scheme)
+    } else {
+        None
+    }
 }
 
-pub fn import_scene(asset: &[u8], width: u32, height: u32) -> Scene {
+/// Replaces `%XX` escapes with the bytes they stand for.
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Where the file an URI refers to should be, `None` for data URIs and
+/// schemes other than `file`.
+fn resolve_uri(base: &Path, uri: &str) -> Option<PathBuf> {
+    match uri_scheme(uri) {
+        None => Some(base.join(percent_decode(uri))),
+        Some(scheme) if scheme.eq_ignore_ascii_case("file") => {
+            let path = percent_decode(&uri[scheme.len() + 1..]);
+            let path = path.strip_prefix("//").unwrap_or(&path);
+            // file:///C:/textures/wood.png
+            let path = match path.as_bytes() {
+                [b'/', drive, b':', ..] if cfg!(windows) && drive.is_ascii_alphabetic() => {
+                    &path[1..]
+                }
+                _ => path,
+            };
+            Some(PathBuf::from(path))
+        }
+        Some(_) => None,
+    }
+}
+
+/// Contents of what an URI refers to. `base` is the directory relative URIs
+/// are resolved against, assets read from memory have none.
+fn read_uri(base: Option<&Path>, uri: &str) -> Result<Vec<u8>> {
+    let invalid = || Error::InvalidUri {
+        uri: uri.chars().take(64).collect(),
+    };
+    if let Some(data) = uri.strip_prefix("data:") {
+        let split = data.find(";base64,").ok_or_else(invalid)?;
+        return base64::decode(&data[split + ";base64,".len()..]).map_err(|_| invalid());
+    }
+    let base = base.ok_or(gltf::Error::ExternalReferenceInSliceImport)?;
+    let path = resolve_uri(base, uri).ok_or_else(invalid)?;
+    fs::read(&path).map_err(|_| Error::MissingFile {
+        uri: String::from(uri),
+        path,
+    })
+}
+
+/// Decodes a PNG or JPEG image the way the gltf importer does.
+fn decode_image(encoded: &[u8]) -> Result<image::Data> {
+    use image_crate::{DynamicImage, GenericImageView};
+    let decoded = image_crate::load_from_memory(encoded).map_err(gltf::Error::Image)?;
+    let format = match decoded {
+        DynamicImage::ImageLuma8(_) => image::Format::R8,
+        DynamicImage::ImageLumaA8(_) => image::Format::R8G8,
+        DynamicImage::ImageRgb8(_) => image::Format::R8G8B8,
+        DynamicImage::ImageRgba8(_) => image::Format::R8G8B8A8,
+        DynamicImage::ImageBgr8(_) => image::Format::B8G8R8,
+        DynamicImage::ImageBgra8(_) => image::Format::B8G8R8A8,
+        DynamicImage::ImageLuma16(_) => image::Format::R16,
+        DynamicImage::ImageLumaA16(_) => image::Format::R16G16,
+        DynamicImage::ImageRgb16(_) => image::Format::R16G16B16,
+        DynamicImage::ImageRgba16(_) => image::Format::R16G16B16A16,
+    };
+    Ok(image::Data {
+        format,
+        width: decoded.width(),
+        height: decoded.height(),
+        pixels: decoded.to_bytes(),
+    })
+}
+
+/// Parses an asset and loads its buffers and images. The gltf importer
+/// doesn't decode percent escapes in file URIs, nor read data URI images
+/// from memory, so resources go through `read_uri` instead.
+fn import_gltf(
+    asset: &[u8],
+    base: Option<&Path>,
+) -> Result<(gltf::Document, Vec<gltf::buffer::Data>, Vec<image::Data>)> {
+    let gltf::Gltf { document, mut blob } = gltf::Gltf::from_slice(asset)?;
+    let mut buffers = Vec::new();
+    for buffer in document.buffers() {
+        let mut data = match buffer.source() {
+            gltf::buffer::Source::Bin => blob.take().ok_or(gltf::Error::MissingBlob)?,
+            gltf::buffer::Source::Uri(uri) => read_uri(base, uri)?,
+        };
+        if data.len() < buffer.length() {
+            return Err(Error::Gltf(gltf::Error::BufferLength {
+                buffer: buffer.index(),
+                expected: buffer.length(),
+                actual: data.len(),
+            }));
+        }
+        data.resize(data.len().next_multiple_of(4), 0);
+        buffers.push(gltf::buffer::Data(data));
+    }
+    let mut images = Vec::new();
+    for image in document.images() {
+        let data = match image.source() {
+            gltf::image::Source::Uri { uri, .. } => decode_image(&read_uri(base, uri)?)?,
+            gltf::image::Source::View { view, .. } => {
+                let buffer = &buffers[view.buffer().index()];
+                decode_image(&buffer[view.offset()..view.offset() + view.length()])?
+            }
+        };
+        images.push(data);
+    }
+    Ok((document, buffers, images))
+}
+
+fn load_scene(
+    gl: &GlContext,
+    document: gltf::Document,
+    json: gltf::json::Value,
+    buffers: &[gltf::buffer::Data],
+    images: &[image::Data],
+    width: u32,
+    height: u32,
+    options: &ImportOptions,
+) -> Result<Scene> {
     let aspect_ratio = width as f32 / height as f32;
-    let (document, buffers, images) = gltf::import_slice(asset).expect("Cannot import asset!");
-    let scene = document.scenes().nth(0).expect("No scenes in asset!");
-    let mut root_node = RealSceneNode::default();
-    let mut camera = None;
-    let mut lights = Vec::new();
+    let active_scene = match &options.scene {
+        SceneSelector::Default => document.default_scene().map_or(0, |scene| scene.index()),
+        SceneSelector::Index(index) => *index,
+        SceneSelector::Name(name) => document
+            .scenes()
+            .position(|scene| scene.name() == Some(name.as_str()))
+            .ok_or_else(|| Error::UnknownScene {
+                scene: name.clone(),
+            })?,
+    };
+    if document.scenes().len() == 0 {
+        return Err(Error::NoScene);
+    }
+    if active_scene >= document.scenes().len() {
+        return Err(Error::UnknownScene {
+            scene: active_scene.to_string(),
+        });
+    }
     fn construct_scene(
+        gl: &GlContext,
         parent: &mut SceneNode,
         node: gltf::Node,
-        mut camera: &mut Option<SceneNode>,
+        mut cameras: &mut Vec<SceneNode>,
         mut lights: &mut Vec<SceneNode>,
         ar: f32,
         buffers: &[gltf::buffer::Data],
         images: &[image::Data],
-    ) {
+        cache: &mut ImportCache,
+        options: &ImportOptions,
+    ) -> Result<()> {
         let mut scene_node = RealSceneNode::default();
         scene_node.id = node.index();
         scene_node.name = String::from(node.name().unwrap_or("NULL"));
@@ -807,35 +2146,38 @@ pub fn import_scene(asset: &[u8], width: u32, height: u32) -> Scene {
         let translation = Translation3::<f32>::new(translation[0], translation[1], translation[2]);
         let rotation = Quaternion::<f32>::new(rotation[3], rotation[0], rotation[1], rotation[2]);
         let rotation = UnitQuaternion::<f32>::from_quaternion(rotation);
-        if scaling[0] != scaling[1] || scaling[1] != scaling[2] {
-            warn!(
-                "Node \"{}\": non uniform scaling is not supported: ({}, {}, {})",
-                scene_node.name, scaling[0], scaling[1], scaling[2]
-            );
-        }
-        let scaling = scaling[0];
-        scene_node.transform.scaling = Some(scaling);
+        scene_node.transform.scaling = Some(na::Vector3::from(scaling));
         scene_node.transform.translation = Some(translation);
         scene_node.transform.rotation = Some(rotation);
         let mut scene_node = Rc::new(RefCell::new(scene_node));
         if let Some(ccamera) = node.camera() {
-            if camera.is_some() {
-                error!("Only one camera allowed!");
-            }
-            *camera = Some(scene_node.clone());
-            let proj = match ccamera.projection() {
-                gltf::camera::Projection::Perspective(p) => Perspective3::<f32>::new(
-                    p.aspect_ratio().unwrap_or(ar),
-                    p.yfov(),
-                    p.znear(),
-                    p.zfar().unwrap_or(1e9),
+            cameras.push(scene_node.clone());
+            let projection = match ccamera.projection() {
+                gltf::camera::Projection::Perspective(p) => Projection::Perspective(
+                    Perspective3::new(ar, p.yfov(), p.znear(), p.zfar().unwrap_or(1e9)),
                 ),
-                _ => unimplemented!(),
+                gltf::camera::Projection::Orthographic(o) => {
+                    Projection::Orthographic(Orthographic3::new(
+                        -o.xmag(),
+                        o.xmag(),
+                        -o.ymag(),
+                        o.ymag(),
+                        o.znear(),
+                        o.zfar(),
+                    ))
+                }
+            };
+            let aspect_ratio = match ccamera.projection() {
+                gltf::camera::Projection::Perspective(p) => p.aspect_ratio(),
+                gltf::camera::Projection::Orthographic(_) => None,
             };
-            scene_node.borrow_mut().camera = Some(Camera {
-                perspective: proj,
+            let mut camera = Camera {
+                projection,
                 name: String::from(ccamera.name().unwrap_or("NULL")),
-            });
+                aspect_ratio,
+            };
+            camera.fit(ar);
+            scene_node.borrow_mut().camera = Some(camera);
         }
         if let Some(light) = node.light() {
             scene_node.borrow_mut().light = Some(Light {
@@ -850,126 +2192,213 @@ pub fn import_scene(asset: &[u8], width: u32, height: u32) -> Scene {
             lights.push(scene_node.clone());
         }
         if let Some(mesh) = node.mesh() {
-            scene_node.borrow_mut().mesh = Some(create_mesh(mesh, buffers, images));
+            // The node may override the default weights of the mesh
+            let targets = mesh
+                .primitives()
+                .map(|primitive| primitive.morph_targets().len())
+                .max()
+                .unwrap_or(0);
+            let mut weights = node
+                .weights()
+                .or_else(|| mesh.weights())
+                .map_or_else(Vec::new, |weights| weights.to_vec());
+            weights.resize(targets, 0.0);
+            scene_node.borrow_mut().weights = weights;
+            let mesh = match cache.meshes.get(&mesh.index()) {
+                Some(mesh) => mesh.clone(),
+                None => {
+                    let index = mesh.index();
+                    let mesh = Rc::new(create_mesh(gl, mesh, buffers, images, cache, options)?);
+                    cache.meshes.insert(index, mesh.clone());
+                    mesh
+                }
+            };
+            scene_node.borrow_mut().mesh = Some(mesh);
+        }
+        if let Some(skin) = node.skin() {
+            scene_node.borrow_mut().skin = Some(load_skin(gl, skin, buffers, cache));
         }
         for child in node.children() {
             construct_scene(
+                gl,
                 &mut scene_node,
                 child,
-                &mut camera,
+                &mut cameras,
                 &mut lights,
                 ar,
                 buffers,
                 images,
-            );
+                cache,
+                options,
+            )?;
         }
         scene_node.borrow_mut().parent = Some(Rc::downgrade(parent));
         parent.borrow_mut().children.push(scene_node);
+        Ok(())
     }
-    root_node.name = String::from("ROOT_NODE");
-    root_node.id = usize::max_value();
-    root_node.transform = Transform::identity();
-    let mut root_node = Rc::new(RefCell::new(root_node));
-    for node in scene.nodes() {
-        construct_scene(
-            &mut root_node,
-            node,
-            &mut camera,
-            &mut lights,
-            aspect_ratio,
-            &buffers,
-            &images,
-        );
+    // Every scene is imported up front, the cache shares meshes and
+    // textures between them
+    let mut cache = ImportCache {
+        json,
+        ..ImportCache::default()
+    };
+    let mut graphs = Vec::new();
+    for scene in document.scenes() {
+        let mut root_node = RealSceneNode::default();
+        root_node.name = String::from("ROOT_NODE");
+        root_node.id = usize::max_value();
+        root_node.transform = Transform::identity();
+        let mut root_node = Rc::new(RefCell::new(root_node));
+        let mut cameras = Vec::new();
+        let mut lights = Vec::new();
+        for node in scene.nodes() {
+            construct_scene(
+                gl,
+                &mut root_node,
+                node,
+                &mut cameras,
+                &mut lights,
+                aspect_ratio,
+                buffers,
+                images,
+                &mut cache,
+                options,
+            )?;
+        }
+        graphs.push(SceneGraph {
+            name: String::from(scene.name().unwrap_or("NULL")),
+            root: root_node,
+            cameras,
+            lights,
+        });
     }
-    let mut animation_map: HashMap<usize, BTreeMap<u64, Transform>> = HashMap::new();
-    let mut animation_time: BTreeSet<u64> = BTreeSet::new();
+    let mut animations = Vec::new();
+    let mut animation_time = Vec::new();
     for animation in document.animations() {
+        let mut channels = Vec::new();
         for channel in animation.channels() {
-            let target_node = channel.target().node().index();
-            let reader = channel.reader(|x| {
-                assert!(match x.source() {
-                    Source::Bin => true,
-                    _ => false,
-                });
-                Some(&buffers[x.index()])
-            });
-            let inputs = reader.read_inputs().expect("No animation input!");
-            let outputs = reader.read_outputs().expect("No animation output!");
-            animation_map.entry(target_node).or_insert(BTreeMap::new());
-            let obj_animation = animation_map.get_mut(&target_node).unwrap();
-            if let ReadOutputs::Scales(iter) = outputs {
-                for anim in inputs.zip(iter) {
-                    let tick = (anim.0 * 1000.0) as u64;
-                    animation_time.insert(tick);
-                    obj_animation.entry(tick).or_insert(Transform::default());
-                    if anim.1[0] != anim.1[1] || anim.1[1] != anim.1[2] {
-                        error!("Non uniform scaling!");
-                    }
-                    obj_animation.get_mut(&tick).unwrap().scaling = Some(anim.1[0]);
-                }
-            } else if let ReadOutputs::Translations(iter) = outputs {
-                for anim in inputs.zip(iter) {
-                    let tick = (anim.0 * 1000.0) as u64;
-                    animation_time.insert(tick);
-                    obj_animation.entry(tick).or_insert(Transform::default());
-                    let translation = Translation3::<f32>::new(anim.1[0], anim.1[1], anim.1[2]);
-                    obj_animation.get_mut(&tick).unwrap().translation = Some(translation);
-                }
-            } else if let ReadOutputs::Rotations(iter) = outputs {
-                let iter = iter.into_f32();
-                for anim in inputs.zip(iter) {
-                    let tick = (anim.0 * 1000.0) as u64;
-                    animation_time.insert(tick);
-                    obj_animation.entry(tick).or_insert(Transform::default());
-                    let rotation =
-                        Quaternion::<f32>::new(anim.1[3], anim.1[0], anim.1[1], anim.1[2]);
-                    let rotation = UnitQuaternion::<f32>::from_quaternion(rotation);
-                    obj_animation.get_mut(&tick).unwrap().rotation = Some(rotation);
-                }
-            }
+            let invalid = || Error::InvalidAnimation {
+                animation: String::from(animation.name().unwrap_or("NULL")),
+                node: String::from(channel.target().node().name().unwrap_or("NULL")),
+            };
+            let channel = Channel::from_gltf(&channel, buffers).ok_or_else(invalid)?;
+            animation_time.extend_from_slice(channel.sampler.times());
+            channels.push(channel);
         }
+        animations.push(Animation::new(
+            String::from(animation.name().unwrap_or("NULL")),
+            channels,
+        ));
     }
-    let mut shdr = Shader::new();
-    shdr.attach(include_str!("shaders/prepare.vert"), ShaderType::Vertex);
-    shdr.attach(include_str!("shaders/prepare.frag"), ShaderType::Fragment);
-    shdr.compile();
-    let mut pbr = Shader::new();
-    pbr.attach(include_str!("shaders/pbr.vert"), ShaderType::Vertex);
-    pbr.attach(include_str!("shaders/pbr.frag"), ShaderType::Fragment);
-    pbr.compile();
-    let mut ssr = Shader::new();
-    ssr.attach(include_str!("shaders/ssr.vert"), ShaderType::Vertex);
-    ssr.attach(include_str!("shaders/ssr.frag"), ShaderType::Fragment);
-    ssr.compile();
-    let mut ssra = Shader::new();
-    ssra.attach(include_str!("shaders/ssra.vert"), ShaderType::Vertex);
-    ssra.attach(include_str!("shaders/ssra.frag"), ShaderType::Fragment);
-    ssra.compile();
-    let animation_time = animation_time.into_iter().collect();
-    Scene {
-        root: root_node,
-        lights: lights,
-        camera: camera.expect("There must be a camera in the scene!"),
+    // Non realtime drawing steps through every keyframe time
+    animation_time.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    animation_time.dedup();
+    if graphs[active_scene].cameras.is_empty() {
+        return Err(Error::NoCamera);
+    }
+    let mut player = AnimationPlayer::new(animations);
+    if !options.stop_animations {
+        player.play_all();
+    }
+    let mut shdr = Shader::new(gl);
+    shdr.try_attach_with_name(
+        include_str!("shaders/prepare.vert"),
+        ShaderType::Vertex,
+        "prepare.vert",
+    )?;
+    shdr.try_attach_with_name(
+        concat!(
+            include_str!("shaders/header.glsl"),
+            include_str!("shaders/material.glsl"),
+            include_str!("shaders/prepare.frag")
+        ),
+        ShaderType::Fragment,
+        "prepare.frag",
+    )?;
+    shdr.try_compile_with_name("prepare")?;
+    let mut pbr = Shader::new(gl);
+    pbr.try_attach_with_name(
+        include_str!("shaders/pbr.vert"),
+        ShaderType::Vertex,
+        "pbr.vert",
+    )?;
+    pbr.try_attach_with_name(
+        concat!(
+            include_str!("shaders/header.glsl"),
+            include_str!("shaders/lighting.glsl"),
+            include_str!("shaders/pbr.frag")
+        ),
+        ShaderType::Fragment,
+        "pbr.frag",
+    )?;
+    pbr.try_compile_with_name("pbr")?;
+    let mut ssr = Shader::new(gl);
+    ssr.try_attach_with_name(
+        include_str!("shaders/ssr.vert"),
+        ShaderType::Vertex,
+        "ssr.vert",
+    )?;
+    ssr.try_attach_with_name(
+        include_str!("shaders/ssr.frag"),
+        ShaderType::Fragment,
+        "ssr.frag",
+    )?;
+    ssr.try_compile_with_name("ssr")?;
+    let mut ssra = Shader::new(gl);
+    ssra.try_attach_with_name(
+        include_str!("shaders/ssra.vert"),
+        ShaderType::Vertex,
+        "ssra.vert",
+    )?;
+    ssra.try_attach_with_name(
+        include_str!("shaders/ssra.frag"),
+        ShaderType::Fragment,
+        "ssra.frag",
+    )?;
+    ssra.try_compile_with_name("ssra")?;
+    let mut forward = Shader::new(gl);
+    forward.try_attach_with_name(
+        include_str!("shaders/prepare.vert"),
+        ShaderType::Vertex,
+        "prepare.vert",
+    )?;
+    forward.try_attach_with_name(
+        concat!(
+            include_str!("shaders/header.glsl"),
+            include_str!("shaders/material.glsl"),
+            include_str!("shaders/lighting.glsl"),
+            include_str!("shaders/forward.frag")
+        ),
+        ShaderType::Fragment,
+        "forward.frag",
+    )?;
+    forward.try_compile_with_name("forward")?;
+    Ok(Scene {
+        gl: gl.clone(),
+        graphs,
+        active_scene,
+        active_camera: 0,
         width,
         height,
-        passes: RenderPasses::new(width as gl::GLsizei, height as gl::GLsizei),
+        passes: RenderPasses::new(gl, width as gl::GLsizei, height as gl::GLsizei)?,
         prepare_shader: shdr,
         pbr_shader: pbr,
         ssr_shader: ssr,
         ssr_apply_shader: ssra,
+        forward_shader: forward,
         fps: VecDeque::new(),
         fps_total: 0.0,
-        last_frame_time: Instant::now(),
-        animation: animation_map,
-        animation_step: animation_time,
-        frame_count: 0,
-        start_time: Instant::now(),
-    }
+        player,
+        clock: Box::new(RealtimeClock::new()),
+        keyframes: KeyframeClock::new(animation_time.into_iter().map(f64::from).collect()),
+        time: None,
+        output_framebuffer: 0,
+    })
 }
 
 impl Scene {
     pub fn get_node(&self, name: &str) -> Option<SceneNode> {
-        let mut queue = vec![self.root.clone()];
+        let mut queue = vec![self.graph().root.clone()];
         while let Some(node) = queue.pop() {
             if name == node.borrow().name {
                 return Some(node.clone());
@@ -981,164 +2410,328 @@ impl Scene {
         None
     }
 
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 || (width, height) == (self.width, self.height) {
+            return;
+        }
+        self.width = width;
+        self.height = height;
+        self.passes
+            .resize(width as gl::GLsizei, height as gl::GLsizei);
+        for camera in self.graphs.iter().flat_map(|graph| &graph.cameras) {
+            if let Some(camera) = camera.borrow_mut().camera.as_mut() {
+                camera.fit(width as f32 / height as f32);
+            }
+        }
+    }
+
+    /// Names of the cameras of the active scene, in document order.
+    pub fn cameras(&self) -> Vec<String> {
+        self.graph()
+            .cameras
+            .iter()
+            .filter_map(|node| Some(node.borrow().camera.as_ref()?.name.clone()))
+            .collect()
+    }
+
+    /// Index in [`Scene::cameras`] of the first camera with the given name.
+    pub fn find_camera(&self, name: &str) -> Option<usize> {
+        self.cameras().iter().position(|camera| camera == name)
+    }
+
+    pub fn active_camera(&self) -> usize {
+        self.active_camera
+    }
+
+    /// Renders the following frames from the camera at `index` in
+    /// [`Scene::cameras`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of range.
+    pub fn set_active_camera(&mut self, index: usize) {
+        assert!(
+            index < self.graph().cameras.len(),
+            "camera index {} out of range",
+            index
+        );
+        self.active_camera = index;
+    }
+
+    /// Renders the following frames from the first camera with the given
+    /// name.
+    pub fn set_active_camera_by_name(&mut self, name: &str) -> Result<()> {
+        let index = self.find_camera(name).ok_or_else(|| Error::UnknownCamera {
+            camera: String::from(name),
+        })?;
+        self.set_active_camera(index);
+        Ok(())
+    }
+
+    /// Names of the scenes in the document, in document order.
+    pub fn scenes(&self) -> Vec<String> {
+        self.graphs.iter().map(|graph| graph.name.clone()).collect()
+    }
+
+    pub fn active_scene(&self) -> usize {
+        self.active_scene
+    }
+
+    /// Shows the scene at `index` in [`Scene::scenes`], rendering it from its
+    /// first camera. Fails if that scene has no camera.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of range.
+    pub fn set_active_scene(&mut self, index: usize) -> Result<()> {
+        assert!(
+            index < self.graphs.len(),
+            "scene index {} out of range",
+            index
+        );
+        if self.graphs[index].cameras.is_empty() {
+            return Err(Error::NoCamera);
+        }
+        self.active_scene = index;
+        self.active_camera = 0;
+        Ok(())
+    }
+
+    /// Replaces the clock realtime drawing reads, a `RealtimeClock` started
+    /// at import by default.
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn animation_player(&self) -> &AnimationPlayer {
+        &self.player
+    }
+
+    pub fn animation_player_mut(&mut self) -> &mut AnimationPlayer {
+        &mut self.player
+    }
+
+    fn graph(&self) -> &SceneGraph {
+        &self.graphs[self.active_scene]
+    }
+
+    pub fn set_output_framebuffer(&mut self, framebuffer: gl::GLuint) {
+        self.output_framebuffer = framebuffer;
+    }
+
+    /// Draws at the time of the scene clock, or of the next keyframe of the
+    /// document when not `realtime`. Returns whether the animations are
+    /// through, once all keyframes were drawn in the latter case.
     pub fn draw(&mut self, frame: &str, realtime: bool) -> bool {
-        fn get_animation(
-            scene: &mut Scene,
-            node: usize,
-            realtime: bool,
-            orig: Transform,
-        ) -> Transform {
-            let mut animation = orig.clone();
-            if !realtime {
-                if let Some(animap) = scene.animation.get(&node) {
-                    if let Some(elem) = animap
-                        .range(0..=scene.animation_step[scene.frame_count])
-                        .rev()
-                        .nth(0)
-                    {
-                        animation.rotation = elem.1.rotation.or(orig.rotation);
-                        animation.translation = elem.1.translation.or(orig.translation);
-                        animation.scaling = elem.1.scaling.or(orig.scaling);
-                    }
+        if realtime {
+            let time = self.clock.tick();
+            self.draw_at(frame, time)
+        } else {
+            let time = self.keyframes.tick();
+            self.draw_at(frame, time);
+            self.keyframes.frame() == 0
+        }
+    }
+
+    /// Draws the scene as it is `time` seconds into its animations and
+    /// returns whether that is past the last keyframe. Animations, and the
+    /// frame rate, only ever see the times given here.
+    pub fn draw_at(&mut self, frame: &str, time: f64) -> bool {
+        debug_assert!(self.gl.is_alive(), "GL context is gone");
+        fn set_lights(shader: &mut Shader, light_info: &[(Point3<f32>, Light)]) {
+            const MAX_LIGHTS: usize = 64;
+            shader.uniform1ui("n_lights", light_info.len().min(MAX_LIGHTS) as u32);
+            for i in 0..light_info.len() {
+                if i >= MAX_LIGHTS {
+                    error!("Too many lights: {}", light_info.len());
+                    break;
                 }
+                let light = &light_info[i];
+                let post = &light.0;
+                let pos: [f32; 4] = [
+                    post[0],
+                    post[1],
+                    post[2],
+                    if light.1.directional { 0.0 } else { 1.0 },
+                ];
+                shader.uniform4f(&format!("light[{}].position", i), pos);
+                shader.uniform3f(&format!("light[{}].color", i), light.1.color);
+                shader.uniform1f(&format!("light[{}].intensity", i), light.1.intensity);
+            }
+        }
+        fn set_world(shader: &mut Shader, world: &na::Matrix4<f32>) {
+            let linear = world.fixed_slice::<na::U3, na::U3>(0, 0).into_owned();
+            let normal = linear
+                .try_inverse()
+                .unwrap_or_else(na::Matrix3::zeros)
+                .transpose();
+            shader.uniformMat4f("world", (*world).into());
+            shader.uniformMat3f("world_normal", normal.into());
+            // Mirroring transforms flip the winding of every triangle
+            let front_face = if linear.determinant() < 0.0 {
+                gl::GL_CW
             } else {
-                if scene.animation_step.len() > 0 {
-                    let tick = (Instant::now().duration_since(scene.start_time).as_millis()
-                        % *scene.animation_step.last().unwrap() as u128)
-                        as u64;
-                    if let Some(animap) = scene.animation.get(&node) {
-                        let prev = animap.range(0..=tick).rev().nth(0).unwrap_or((&0, &orig));
-                        let next = animap
-                            .range((Excluded(&tick), Included(&u64::max_value())))
-                            .nth(0)
-                            .unwrap_or_else(|| animap.iter().rev().nth(0).unwrap_or((&0, &orig)));
-                        let alpha = if next.0 - prev.0 == 0 {
-                            0.0
-                        } else {
-                            (tick - prev.0) as f32 / (next.0 - prev.0) as f32
-                        };
-                        animation = prev.1.or(orig).lerp(next.1.or(orig), alpha);
-                    }
-                }
+                gl::GL_CCW
+            };
+            unsafe {
+                gl::FrontFace(front_face);
+            }
+        }
+        fn bind_skin(shader: &mut Shader, skin: Option<&Skin>) {
+            shader.uniform1i("joint_sampler", 5);
+            unsafe {
+                gl::ActiveTexture(gl::GL_TEXTURE5);
+                gl::BindTexture(gl::GL_TEXTURE_2D, skin.map_or(0, |skin| skin.texture.id()));
+                gl::ActiveTexture(gl::GL_TEXTURE0);
             }
-            animation
         }
-        const MAX_LIGHTS: usize = 64;
+        unsafe {
+            gl::Enable(gl::GL_DEPTH_TEST);
+            gl::Viewport(0, 0, self.width as gl::GLsizei, self.height as gl::GLsizei);
+        }
         self.passes.bind();
         self.prepare_shader.activate();
+        // Time may go backwards as well, the poses only depend on it
+        let elapsed = self.time.map(|last| time - last);
+        self.time = Some(time);
+        self.player.set_time(time);
+        let poses = self.player.evaluate_at(time);
         let mut light_info = Vec::new();
-        let mut queue = vec![(self.root.clone(), Transform::identity())];
-        let mut camstruct = None;
+        let mut worlds = HashMap::new();
+        let mut mesh_nodes = Vec::new();
+        let mut queue = vec![(self.graph().root.clone(), na::Matrix4::identity())];
+        let mut view = None;
         while let Some(mut node) = queue.pop() {
-            node.1 = node.1.compose(get_animation(
-                self,
-                node.0.borrow().id,
-                realtime,
-                node.0.borrow().transform.clone(),
-            ));
-            if let Some(camera) = &node.0.borrow().camera {
-                let trans_matrix = node.1.get_similarity();
-                let projection = camera.perspective.to_projective();
-                camstruct = Some((
-                    trans_matrix.transform_point(&Point3::<f32>::new(0.0, 0.0, 0.0)),
-                    projection * trans_matrix.inverse(),
-                ));
+            let mut transform = node.0.borrow().transform;
+            let mut weights = node.0.borrow().weights.clone();
+            poses.apply(node.0.borrow().id, &mut transform, &mut weights);
+            node.1 *= transform.matrix();
+            if Rc::ptr_eq(&node.0, &self.graph().cameras[self.active_camera]) {
+                let projection = node.0.borrow().camera.as_ref().unwrap().projection;
+                let trans_matrix = node.1;
+                view = Some(View {
+                    position: trans_matrix.transform_point(&Point3::<f32>::new(0.0, 0.0, 0.0)),
+                    direction: trans_matrix
+                        .transform_vector(&-na::Vector3::z())
+                        .normalize(),
+                    orthographic: projection.is_orthographic(),
+                    matrix: projection.to_homogeneous()
+                        * trans_matrix
+                            .try_inverse()
+                            .unwrap_or_else(na::Matrix4::identity),
+                });
             }
             if let Some(light) = &node.0.borrow().light {
-                let trans_matrix = node.1.get_similarity();
+                let trans_matrix = node.1;
                 let point = trans_matrix.transform_point(&Point3::<f32>::new(0.0, 0.0, 0.0));
                 light_info.push((point, light.clone()));
             }
+            if node.0.borrow().mesh.is_some() {
+                mesh_nodes.push((node.0.clone(), node.1, weights));
+            }
+            worlds.insert(node.0.borrow().id, node.1);
             for child in &node.0.borrow().children {
                 queue.push((child.clone(), node.1.clone()));
             }
         }
-        let camstruct = camstruct.unwrap();
-        let cp = camstruct.0;
-        let cm = camstruct.1;
-        self.prepare_shader
-            .uniformMat4f("camera", cm.to_homogeneous().into());
-        let mut queue = vec![(self.root.clone(), Transform::identity())];
-        while let Some(mut node) = queue.pop() {
-            node.1 = node.1.compose(get_animation(
-                self,
-                node.0.borrow().id,
-                realtime,
-                node.0.borrow().transform.clone(),
-            ));
-            if let Some(mesh) = &node.0.borrow().mesh {
-                let trans_matrix = node.1.get_similarity().to_homogeneous().into();
-                self.prepare_shader.uniformMat4f("world", trans_matrix);
-                mesh.draw(&mut self.prepare_shader);
+        let view = view.unwrap();
+        let cm = view.matrix;
+        self.prepare_shader.uniformMat4f("camera", cm.into());
+        // Each skin is uploaded once, however many meshes it deforms
+        let mut skins: Vec<Rc<Skin>> = Vec::new();
+        for (node, _, _) in &mesh_nodes {
+            if let Some(skin) = &node.borrow().skin {
+                if !skins.iter().any(|other| Rc::ptr_eq(other, skin)) {
+                    skins.push(skin.clone());
+                }
             }
-            for child in &node.0.borrow().children {
-                queue.push((child.clone(), node.1.clone()));
+        }
+        for skin in &skins {
+            skin.update(&worlds);
+        }
+        let mut transparent = Vec::new();
+        for (node, trans_matrix, weights) in mesh_nodes {
+            let node_ref = node.borrow();
+            let mesh = node_ref.mesh.as_ref().unwrap();
+            // Skinned vertices are placed by the joints alone, the transform of
+            // the node holding the mesh doesn't apply
+            let draw_matrix = match &node_ref.skin {
+                Some(_) => na::Matrix4::identity(),
+                None => trans_matrix,
+            };
+            bind_skin(&mut self.prepare_shader, node_ref.skin.as_deref());
+            set_world(&mut self.prepare_shader, &draw_matrix);
+            mesh.draw(&mut self.prepare_shader, &weights);
+            for (i, rd) in mesh.data.iter().enumerate() {
+                if rd.is_transparent() {
+                    let center = trans_matrix.transform_point(&rd.center);
+                    let distance = if view.orthographic {
+                        (center - view.position).dot(&view.direction)
+                    } else {
+                        (center - view.position).norm_squared()
+                    };
+                    transparent.push((distance, draw_matrix, weights.clone(), node.clone(), i));
+                }
             }
         }
+        // The screen space passes draw a clockwise quad
+        unsafe {
+            gl::Disable(gl::GL_CULL_FACE);
+            gl::FrontFace(gl::GL_CCW);
+        }
         // PBR PASS
         let shader = &mut self.pbr_shader;
         shader.activate();
         self.passes.bind_pbr(shader);
-        shader.uniform3f("camera_pos", [cp[0], cp[1], cp[2]]);
-        shader.uniform1ui("n_lights", light_info.len() as u32);
-        for i in 0..light_info.len() {
-            if i >= MAX_LIGHTS {
-                error!("Too many lights: {}", light_info.len());
-                break;
-            }
-            let light = &light_info[i];
-            let post = &light.0;
-            let pos: [f32; 4] = [
-                post[0],
-                post[1],
-                post[2],
-                if light.1.directional { 0.0 } else { 1.0 },
-            ];
-            shader.uniform4f(&format!("light[{}].position", i), pos);
-            shader.uniform3f(&format!("light[{}].color", i), light.1.color);
-            shader.uniform1f(&format!("light[{}].intensity", i), light.1.intensity);
-        }
+        view.set_uniforms(shader);
+        set_lights(shader, &light_info);
         self.passes.print_quad();
         // SSR PASS
         let shader = &mut self.ssr_shader;
         shader.activate();
         self.passes.bind_ssr(shader);
-        shader.uniform3f("camera_pos", [cp[0], cp[1], cp[2]]);
-        shader.uniformMat4f("camera", cm.to_homogeneous().into());
+        view.set_uniforms(shader);
+        shader.uniformMat4f("camera", cm.into());
         self.passes.print_quad();
         // SSR-APPLY
         let shader = &mut self.ssr_apply_shader;
         shader.activate();
         self.passes.bind_ssr_apply(shader);
         self.passes.print_quad();
-        // FINAL PASS
-        self.passes.print_buffer(frame);
-        let now = Instant::now();
-        let elapsed = now.duration_since(self.last_frame_time).as_secs_f64();
-        self.fps_total += elapsed;
-        self.fps.push_back(elapsed);
-        self.last_frame_time = now;
-        if self.fps.len() > 127 {
-            self.fps_total -= self.fps.pop_front().expect("cannot fail");
-        }
-        self.frame_count += 1;
-        if realtime {
-            if self.animation_step.len() == 0
-                || Instant::now().duration_since(self.start_time).as_millis()
-                    >= *self.animation_step.last().unwrap() as u128
-            {
-                true
-            } else {
-                false
+        // FORWARD PASS
+        if !transparent.is_empty() {
+            // Back to front
+            transparent.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+            let shader = &mut self.forward_shader;
+            shader.activate();
+            self.passes.bind_forward();
+            shader.uniformMat4f("camera", cm.into());
+            view.set_uniforms(shader);
+            set_lights(shader, &light_info);
+            for (_, trans_matrix, weights, node, i) in &transparent {
+                bind_skin(shader, node.borrow().skin.as_deref());
+                set_world(shader, trans_matrix);
+                node.borrow().mesh.as_ref().unwrap().data[*i].draw(shader, weights);
             }
-        } else {
-            if self.animation_step.len() == 0 || self.frame_count == self.animation_step.len() {
-                self.frame_count = 0;
-                true
-            } else {
-                false
+            self.passes.unbind_forward();
+            unsafe {
+                gl::FrontFace(gl::GL_CCW);
+            }
+        }
+        // FINAL PASS
+        self.passes.print_buffer(frame, self.output_framebuffer);
+        if let Some(elapsed) = elapsed.filter(|elapsed| *elapsed > 0.0) {
+            self.fps_total += elapsed;
+            self.fps.push_back(elapsed);
+            if self.fps.len() > 127 {
+                self.fps_total -= self.fps.pop_front().expect("cannot fail");
             }
         }
+        time >= self.keyframes.times().last().copied().unwrap_or(0.0)
     }
 
     pub fn get_fps(&self) -> f64 {
@@ -1149,3 +2742,223 @@ impl Scene {
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1.0e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn quad_tangents() {
+        // Unit quad facing +z, two triangles sharing a diagonal
+        let pos = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let norm = [[0.0, 0.0, 1.0]; 4];
+        let indices = [0, 1, 2, 0, 2, 3];
+        // u follows x and v follows y
+        let texcoord = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        for tangent in generate_tangents(&pos, &norm, &texcoord, Some(&indices)) {
+            assert_close(&tangent, &[1.0, 0.0, 0.0, 1.0]);
+        }
+        // Mirrored along u: the tangent turns around and so does the handedness
+        let mirrored = [[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]];
+        for tangent in generate_tangents(&pos, &norm, &mirrored, Some(&indices)) {
+            assert_close(&tangent, &[-1.0, 0.0, 0.0, -1.0]);
+        }
+        // The same triangles without indices
+        let flat: Vec<_> = indices.iter().map(|&i| pos[i as usize]).collect();
+        let flat_texcoord: Vec<_> = indices.iter().map(|&i| texcoord[i as usize]).collect();
+        let tangents = generate_tangents(&flat, &[[0.0, 0.0, 1.0]; 6], &flat_texcoord, None);
+        assert_eq!(tangents.len(), 6);
+        for tangent in tangents {
+            assert_close(&tangent, &[1.0, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn normal_texture_transform_from_json() {
+        let json = raw_json(
+            br#"{"materials": [{"normalTexture": {"index": 0, "extensions": {
+                "KHR_texture_transform": {"offset": [0.5, 0.25], "rotation": 1.5, "texCoord": 1}
+            }}}]}"#,
+        );
+        let transform = json
+            .pointer("/materials/0/normalTexture/extensions/KHR_texture_transform")
+            .unwrap();
+        assert_eq!(
+            raw_texture_transform(transform),
+            ([0.5, 0.25], 1.5, [1.0, 1.0], Some(1))
+        );
+        assert_eq!(
+            raw_texture_transform(&gltf::json::Value::Null),
+            ([0.0, 0.0], 0.0, [1.0, 1.0], None)
+        );
+        assert_eq!(raw_json(b"glTF garbage"), gltf::json::Value::Null);
+    }
+
+    #[test]
+    fn uris_resolve_to_files() {
+        let base = Path::new("assets");
+        assert_eq!(
+            resolve_uri(base, "my%20texture.png"),
+            Some(base.join("my texture.png"))
+        );
+        assert_eq!(
+            resolve_uri(base, "textures/wood%2Bmetal.png"),
+            Some(base.join("textures/wood+metal.png"))
+        );
+        assert_eq!(
+            resolve_uri(base, "file:///tmp/a%20b.bin"),
+            Some(PathBuf::from("/tmp/a b.bin"))
+        );
+        assert_eq!(
+            resolve_uri(base, "data:application/octet-stream;base64,AAAA"),
+            None
+        );
+        assert_eq!(resolve_uri(base, "https://example.com/a.bin"), None);
+        // Drive letters aren't schemes
+        assert_eq!(uri_scheme(r"C:\textures\wood.png"), None);
+        assert_eq!(
+            resolve_uri(base, r"C:\textures\wood.png"),
+            Some(base.join(r"C:\textures\wood.png"))
+        );
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%e2%82%ac%zz"), "\u{20ac}%zz");
+    }
+
+    #[test]
+    fn external_files_are_read() {
+        let dir = std::env::temp_dir().join(format!("scarlet-import-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("my buffer.bin"), [1u8, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        // 1x1 PNG
+        let png = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8BQDwAEhQGAhKmMIQAAAABJRU5ErkJggg==";
+        let asset = format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "buffers": [{{"uri": "my%20buffer.bin", "byteLength": 8}}],
+                "bufferViews": [{{"buffer": 0, "byteOffset": 4, "byteLength": 4}}],
+                "images": [{{"uri": "data:image/png;base64,{}"}}]
+            }}"#,
+            png
+        );
+        let (document, buffers, images) = import_gltf(asset.as_bytes(), Some(&dir)).unwrap();
+        let view = document.views().next().unwrap();
+        assert_eq!(
+            &buffers[0][view.offset()..view.offset() + view.length()],
+            &[5, 6, 7, 8]
+        );
+        assert_eq!((images[0].width, images[0].height), (1, 1));
+        // Memory assets can't refer to files, but can use data URIs
+        assert!(import_gltf(asset.as_bytes(), None).is_err());
+        let embedded = asset.replace(
+            "my%20buffer.bin",
+            "data:application/octet-stream;base64,AQIDBAUGBwg=",
+        );
+        let (_, buffers, _) = import_gltf(embedded.as_bytes(), None).unwrap();
+        assert_eq!(&buffers[0][..8], &[1, 2, 3, 4, 5, 6, 7, 8]);
+        let missing = asset.replace("my%20buffer", "missing");
+        match import_gltf(missing.as_bytes(), Some(&dir)) {
+            Err(Error::MissingFile { path, .. }) => assert_eq!(path, dir.join("missing.bin")),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn document_aspect_ratio_wins() {
+        let mut camera = Camera {
+            projection: Projection::Perspective(Perspective3::new(1.0, 0.5, 0.1, 100.0)),
+            name: String::from("Shot"),
+            aspect_ratio: Some(2.35),
+        };
+        camera.fit(16.0 / 9.0);
+        let aspect = |camera: &Camera| match camera.projection {
+            Projection::Perspective(p) => p.aspect(),
+            Projection::Orthographic(_) => unreachable!(),
+        };
+        assert!((aspect(&camera) - 2.35).abs() < 1.0e-5);
+        camera.aspect_ratio = None;
+        camera.fit(16.0 / 9.0);
+        assert!((aspect(&camera) - 16.0 / 9.0).abs() < 1.0e-5);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_similarity_helpers() {
+        let a = Transform {
+            scaling: Some(na::Vector3::new(2.0, 2.0, 2.0)),
+            rotation: Some(UnitQuaternion::from_euler_angles(0.0, 0.0, 0.5)),
+            translation: Some(Translation3::new(1.0, 2.0, 3.0)),
+        };
+        let b = Transform {
+            scaling: Some(na::Vector3::new(1.0, 3.0, 1.0)),
+            rotation: Some(UnitQuaternion::from_euler_angles(0.3, 0.0, 0.0)),
+            translation: Some(Translation3::new(1.0, 0.0, 0.0)),
+        };
+        let similarity = a.get_similarity();
+        assert!((similarity.scaling() - 2.0).abs() < 1.0e-5);
+        assert!((similarity.to_homogeneous() - a.matrix()).norm() < 1.0e-5);
+        assert!((a.compose(b).matrix() - a.matrix() * b.matrix()).norm() < 1.0e-5);
+    }
+
+    #[test]
+    fn morph_targets_blend_on_the_cpu() {
+        // Ten single vertex targets, more than the vertex shader blends
+        let mut weights: Vec<f32> = (0..10).map(|i| (i as f32 - 4.5) / 10.0).collect();
+        weights[3] = 0.0;
+        let active = active_morph_targets(&weights);
+        assert_eq!(active.len(), 9);
+        assert!(active.len() > MAX_ACTIVE_MORPH_TARGETS);
+        assert!(active.windows(2).all(|w| w[0].1.abs() >= w[1].1.abs()));
+        let deltas: Vec<[Vec<[f32; 3]>; 2]> = (0..10)
+            .map(|i| [vec![[i as f32, 0.0, 1.0]], vec![[0.0, 1.0, 0.0]]])
+            .collect();
+        let mut base = vec![0.0; VERTEX_SIZE];
+        base[2] = 5.0;
+        base[6] = 0.5;
+        let blended = blend_morph_targets(&base, &deltas, &active);
+        let mut expected = base.clone();
+        for (i, weight) in weights.iter().enumerate() {
+            expected[0] += weight * i as f32;
+            expected[2] += weight;
+            expected[4] += weight;
+        }
+        assert_close(&blended, &expected);
+        assert_close(&blend_morph_targets(&base, &deltas, &[]), &base);
+    }
+
+    #[test]
+    fn transform_lerp_keeps_missing_properties() {
+        let a = Transform {
+            scaling: None,
+            rotation: Some(UnitQuaternion::from_euler_angles(0.0, 0.0, 0.2)),
+            translation: Some(Translation3::new(0.0, 2.0, 0.0)),
+        };
+        // 0.4 radians about z, stored on the far side of the hypersphere
+        let turned = UnitQuaternion::from_euler_angles(0.0, 0.0, 0.4);
+        let b = Transform {
+            scaling: Some(na::Vector3::new(2.0, 2.0, 2.0)),
+            rotation: Some(UnitQuaternion::new_unchecked(-turned.into_inner())),
+            translation: None,
+        };
+        let c = a.lerp(b, 0.5);
+        assert_close(c.scaling.unwrap().as_slice(), &[2.0, 2.0, 2.0]);
+        assert_close(c.translation.unwrap().vector.as_slice(), &[0.0, 2.0, 0.0]);
+        assert!((c.rotation.unwrap().angle() - 0.3).abs() < 1.0e-5);
+        assert!(Transform::default()
+            .lerp(Transform::default(), 0.5)
+            .rotation
+            .is_none());
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{have_debug_gl, have_gl};
+use crate::context::GlContext;
+use crate::error::{Error, Result};
 use glad_gles2::gl;
 use std::ffi::{CStr, CString};
 use std::ptr::null;
@@ -18,41 +19,71 @@ impl ShaderType {
     }
 }
 
+/// Shader or program object, deleted when dropped so that failed compiles
+/// and links don't leak it.
 #[derive(Debug)]
-pub struct Shader {
-    shader: Vec<gl::GLuint>,
-    program: gl::GLuint,
-    ready: bool,
+struct ShaderObject {
+    gl: GlContext,
+    name: gl::GLuint,
+    program: bool,
 }
 
-impl Drop for Shader {
+impl ShaderObject {
+    fn shader(gl: &GlContext, kind: &ShaderType) -> ShaderObject {
+        ShaderObject {
+            gl: gl.clone(),
+            name: unsafe { gl::CreateShader(kind.gl_type()) },
+            program: false,
+        }
+    }
+
+    fn program(gl: &GlContext) -> ShaderObject {
+        ShaderObject {
+            gl: gl.clone(),
+            name: unsafe { gl::CreateProgram() },
+            program: true,
+        }
+    }
+}
+
+impl Drop for ShaderObject {
     fn drop(&mut self) {
-        if have_gl() {
+        if self.gl.is_alive() {
             unsafe {
-                if self.ready {
-                    gl::DeleteProgram(self.program);
-                }
-                for i in &self.shader {
-                    gl::DeleteShader(*i);
+                if self.program {
+                    gl::DeleteProgram(self.name);
+                } else {
+                    gl::DeleteShader(self.name);
                 }
             }
         }
     }
 }
 
+#[derive(Debug)]
+pub struct Shader {
+    gl: GlContext,
+    shader: Vec<ShaderObject>,
+    program: Option<ShaderObject>,
+}
+
 impl Shader {
-    pub fn new() -> Shader {
+    pub fn new(gl: &GlContext) -> Shader {
         Shader {
+            gl: gl.clone(),
             shader: Vec::new(),
-            program: 0,
-            ready: false,
+            program: None,
         }
     }
 
+    fn program(&self) -> gl::GLuint {
+        self.program.as_ref().map_or(0, |p| p.name)
+    }
+
     pub fn uniform1ui(&mut self, name: &str, value: u32) {
         let name = CString::new(name).expect("Cannot convert to CString");
         unsafe {
-            let location = gl::GetUniformLocation(self.program, name.as_ptr());
+            let location = gl::GetUniformLocation(self.program(), name.as_ptr());
             gl::Uniform1ui(location, value);
         }
     }
@@ -60,7 +91,7 @@ impl Shader {
     pub fn uniform1i(&mut self, name: &str, value: i32) {
         let name = CString::new(name).expect("Cannot convert to CString");
         unsafe {
-            let location = gl::GetUniformLocation(self.program, name.as_ptr());
+            let location = gl::GetUniformLocation(self.program(), name.as_ptr());
             gl::Uniform1i(location, value);
         }
     }
@@ -68,7 +99,7 @@ impl Shader {
     pub fn uniform1f(&mut self, name: &str, value: f32) {
         let name = CString::new(name).expect("Cannot convert to CString");
         unsafe {
-            let location = gl::GetUniformLocation(self.program, name.as_ptr());
+            let location = gl::GetUniformLocation(self.program(), name.as_ptr());
             gl::Uniform1f(location, value);
         }
     }
@@ -76,7 +107,7 @@ impl Shader {
     pub fn uniform2f(&mut self, name: &str, value: [f32; 2]) {
         let name = CString::new(name).expect("Cannot convert to CString");
         unsafe {
-            let location = gl::GetUniformLocation(self.program, name.as_ptr());
+            let location = gl::GetUniformLocation(self.program(), name.as_ptr());
             gl::Uniform2fv(location, 1, value.as_ptr());
         }
     }
@@ -84,7 +115,7 @@ impl Shader {
     pub fn uniform4f(&mut self, name: &str, value: [f32; 4]) {
         let name = CString::new(name).expect("Cannot convert to CString");
         unsafe {
-            let location = gl::GetUniformLocation(self.program, name.as_ptr());
+            let location = gl::GetUniformLocation(self.program(), name.as_ptr());
             gl::Uniform4fv(location, 1, value.as_ptr());
         }
     }
@@ -92,11 +123,26 @@ impl Shader {
     pub fn uniform3f(&mut self, name: &str, value: [f32; 3]) {
         let name = CString::new(name).expect("Cannot convert to CString");
         unsafe {
-            let location = gl::GetUniformLocation(self.program, name.as_ptr());
+            let location = gl::GetUniformLocation(self.program(), name.as_ptr());
             gl::Uniform3fv(location, 1, value.as_ptr());
         }
     }
 
+    #[allow(non_snake_case)]
+    pub fn uniformMat3f(&mut self, name: &str, value: [[f32; 3]; 3]) {
+        let mut mvalue = Vec::new();
+        for i in 0..3 {
+            for j in 0..3 {
+                mvalue.push(value[i][j]);
+            }
+        }
+        let name = CString::new(name).expect("Cannot convert to CString");
+        unsafe {
+            let location = gl::GetUniformLocation(self.program(), name.as_ptr());
+            gl::UniformMatrix3fv(location, 1, 0, mvalue.as_ptr());
+        }
+    }
+
     #[allow(non_snake_case)]
     pub fn uniformMat4f(&mut self, name: &str, value: [[f32; 4]; 4]) {
         let mut mvalue = Vec::new();
@@ -107,15 +153,16 @@ impl Shader {
         }
         let name = CString::new(name).expect("Cannot convert to CString");
         unsafe {
-            let location = gl::GetUniformLocation(self.program, name.as_ptr());
+            let location = gl::GetUniformLocation(self.program(), name.as_ptr());
             gl::UniformMatrix4fv(location, 1, 0, mvalue.as_ptr());
         }
     }
 
     pub fn activate(&self) {
-        if self.ready {
+        debug_assert!(self.gl.is_alive(), "GL context is gone");
+        if let Some(program) = &self.program {
             unsafe {
-                gl::UseProgram(self.program);
+                gl::UseProgram(program.name);
             }
         }
     }
@@ -125,11 +172,27 @@ impl Shader {
     }
 
     pub fn attach_with_name(&mut self, source: &str, kind: ShaderType, debug_name: &str) {
-        let shdr;
-        let source = CString::new(source).unwrap();
+        self.try_attach_with_name(source, kind, debug_name)
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    pub fn try_attach(&mut self, source: &str, kind: ShaderType) -> Result<()> {
+        self.try_attach_with_name(source, kind, "NULL")
+    }
+
+    pub fn try_attach_with_name(
+        &mut self,
+        source: &str,
+        kind: ShaderType,
+        debug_name: &str,
+    ) -> Result<()> {
+        let source = CString::new(source).map_err(|_| Error::ShaderSource {
+            name: String::from(debug_name),
+        })?;
+        let object = ShaderObject::shader(&self.gl, &kind);
+        let shdr = object.name;
         unsafe {
-            shdr = gl::CreateShader(kind.gl_type());
-            if have_debug_gl() {
+            if self.gl.is_debug() {
                 let debug_name = debug_name.as_bytes();
                 gl::ObjectLabelKHR(
                     gl::GL_SHADER_KHR,
@@ -147,13 +210,14 @@ impl Shader {
                 let size = status as usize;
                 let mut buffer = vec![0_u8; size];
                 gl::GetShaderInfoLog(shdr, status, &mut status, buffer.as_mut_ptr() as *mut i8);
-                let cstr = CStr::from_bytes_with_nul(&buffer)
-                    .unwrap()
-                    .to_string_lossy();
-                panic!("Cannot compile shader: {}", cstr);
+                return Err(Error::ShaderCompile {
+                    name: String::from(debug_name),
+                    log: info_log(&buffer),
+                });
             }
         }
-        self.shader.push(shdr);
+        self.shader.push(object);
+        Ok(())
     }
 
     pub fn compile(&mut self) {
@@ -161,13 +225,22 @@ impl Shader {
     }
 
     pub fn compile_with_name(&mut self, debug_name: &str) {
-        if self.ready {
-            return;
+        self.try_compile_with_name(debug_name)
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    pub fn try_compile(&mut self) -> Result<()> {
+        self.try_compile_with_name("NULL")
+    }
+
+    pub fn try_compile_with_name(&mut self, debug_name: &str) -> Result<()> {
+        if self.program.is_some() {
+            return Ok(());
         }
-        let program;
+        let object = ShaderObject::program(&self.gl);
+        let program = object.name;
         unsafe {
-            program = gl::CreateProgram();
-            if have_debug_gl() {
+            if self.gl.is_debug() {
                 let debug_name = debug_name.as_bytes();
                 gl::ObjectLabelKHR(
                     gl::GL_PROGRAM_KHR,
@@ -177,7 +250,7 @@ impl Shader {
                 );
             }
             for i in &self.shader {
-                gl::AttachShader(program, *i);
+                gl::AttachShader(program, i.name);
             }
             gl::LinkProgram(program);
             let mut status: gl::GLint = 0;
@@ -187,13 +260,19 @@ impl Shader {
                 let size = status as usize;
                 let mut buffer = vec![0_u8; size];
                 gl::GetProgramInfoLog(program, status, &mut status, buffer.as_mut_ptr() as *mut i8);
-                let cstr = CStr::from_bytes_with_nul(&buffer)
-                    .unwrap()
-                    .to_string_lossy();
-                panic!("Cannot link shader: {}", cstr);
+                return Err(Error::ShaderLink {
+                    name: String::from(debug_name),
+                    log: info_log(&buffer),
+                });
             }
         }
-        self.program = program;
-        self.ready = true;
+        self.program = Some(object);
+        Ok(())
     }
 }
+
+fn info_log(buffer: &[u8]) -> String {
+    CStr::from_bytes_with_nul(buffer)
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| String::from_utf8_lossy(buffer).into_owned())
+}
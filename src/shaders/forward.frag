@@ -0,0 +1,14 @@
+
+in vec3 pos;
+in vec3 norm;
+in vec2 texcoord;
+in vec4 tangent;
+in vec2 texcoord1;
+
+out vec4 color_output;
+
+void main() {
+    Surface s = sample_material(norm, tangent, texcoord, texcoord1);
+    vec3 color = shade(pos, s.normal, s.albedo, s.metalness, s.roughness, s.occlusion, s.emissive);
+    color_output = vec4(color, s.alpha);
+}
@@ -0,0 +1,83 @@
+
+#define ALPHA_OPAQUE 0
+#define ALPHA_MASK 1
+#define ALPHA_BLEND 2
+
+struct Material {
+    sampler2D albedo_sampler;
+    int albedo_uv;
+    mat3 albedo_transform;
+    vec4 albedo;
+    float metalness;
+    float roughness;
+    sampler2D normal_sampler;
+    int normal_uv;
+    mat3 normal_transform;
+    bool has_normal;
+    float normal_scale;
+    sampler2D metallic_roughness_sampler;
+    int metallic_roughness_uv;
+    mat3 metallic_roughness_transform;
+    sampler2D occlusion_sampler;
+    int occlusion_uv;
+    mat3 occlusion_transform;
+    float occlusion_strength;
+    sampler2D emissive_sampler;
+    int emissive_uv;
+    mat3 emissive_transform;
+    vec3 emissive;
+    int alpha_mode;
+    float alpha_cutoff;
+    bool double_sided;
+};
+
+uniform Material material;
+
+struct Surface {
+    vec3 normal;
+    vec3 albedo;
+    float alpha;
+    float metalness;
+    float roughness;
+    float occlusion;
+    vec3 emissive;
+};
+
+vec2 slot_uv(int set, mat3 transform, vec2 texcoord0, vec2 texcoord1) {
+    vec2 uv = set == 1 ? texcoord1 : texcoord0;
+    return (transform * vec3(uv, 1.0)).xy;
+}
+
+Surface sample_material(vec3 norm, vec4 tangent, vec2 texcoord0, vec2 texcoord1) {
+    Surface s;
+    vec2 albedo_uv = slot_uv(material.albedo_uv, material.albedo_transform, texcoord0, texcoord1);
+    vec2 normal_uv = slot_uv(material.normal_uv, material.normal_transform, texcoord0, texcoord1);
+    vec2 metallic_roughness_uv = slot_uv(material.metallic_roughness_uv,
+                                         material.metallic_roughness_transform,
+                                         texcoord0, texcoord1);
+    vec2 occlusion_uv = slot_uv(material.occlusion_uv, material.occlusion_transform, texcoord0, texcoord1);
+    vec2 emissive_uv = slot_uv(material.emissive_uv, material.emissive_transform, texcoord0, texcoord1);
+    vec3 n = normalize(norm);
+    // Back faces of double sided materials are shaded with the flipped
+    // tangent frame
+    float side = material.double_sided && !gl_FrontFacing ? -1.0 : 1.0;
+    if (material.has_normal) {
+        vec3 t = normalize(tangent.xyz - dot(tangent.xyz, n) * n);
+        vec3 b = cross(n, t) * tangent.w;
+        vec3 tn = texture(material.normal_sampler, normal_uv).rgb * 2.0 - 1.0;
+        tn.xy *= material.normal_scale;
+        n = normalize(mat3(t, b, n) * tn);
+    }
+    n *= side;
+    s.normal = n;
+    vec4 albedo = texture(material.albedo_sampler, albedo_uv);
+    s.albedo = material.albedo.rgb * pow(albedo.rgb, vec3(2.2));
+    s.alpha = material.albedo.a * albedo.a;
+    vec4 metallic_roughness = texture(material.metallic_roughness_sampler, metallic_roughness_uv);
+    s.metalness = material.metalness * metallic_roughness.b;
+    s.roughness = material.roughness * metallic_roughness.g;
+    float occlusion = texture(material.occlusion_sampler, occlusion_uv).r;
+    s.occlusion = 1.0 + material.occlusion_strength * (occlusion - 1.0);
+    s.emissive = material.emissive * pow(texture(material.emissive_sampler, emissive_uv).rgb, vec3(2.2));
+    return s;
+}
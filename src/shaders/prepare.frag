@@ -1,30 +1,26 @@
-#version 300 es
-
-precision mediump float;
 
 in vec3 pos;
 in vec3 norm;
 in vec2 texcoord;
+in vec4 tangent;
+in vec2 texcoord1;
 
 layout (location = 0) out vec3 g_position;
 layout (location = 1) out vec3 g_normal;
 layout (location = 2) out vec3 g_albedo;
 layout (location = 3) out float g_metalness;
 layout (location = 4) out float g_roughness;
-
-struct Material {
-    sampler2D albedo_sampler;
-    vec4 albedo;
-    float metalness;
-    float roughness;
-};
-
-uniform Material material;
+layout (location = 5) out float g_occlusion;
+layout (location = 6) out vec3 g_emissive;
 
 void main() {
+    Surface s = sample_material(norm, tangent, texcoord, texcoord1);
+    if (material.alpha_mode == ALPHA_MASK && s.alpha < material.alpha_cutoff) discard;
     g_position = pos;
-    g_normal = normalize(norm);
-    g_albedo = material.albedo.rgb * pow(texture(material.albedo_sampler, texcoord).rgb, vec3(2.2));
-    g_metalness = material.metalness;
-    g_roughness = material.roughness;
+    g_normal = s.normal;
+    g_albedo = s.albedo;
+    g_metalness = s.metalness;
+    g_roughness = s.roughness;
+    g_occlusion = s.occlusion;
+    g_emissive = s.emissive;
 }
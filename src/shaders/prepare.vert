@@ -3,20 +3,74 @@
 precision mediump float;
 
 uniform mat4 world;
+uniform mat3 world_normal;
+uniform bool skinned;
+uniform highp sampler2D joint_sampler;
+uniform bool morphed;
+uniform highp sampler2D morph_sampler;
+uniform int morph_rows;
+uniform int n_morph_weights;
+// MAX_ACTIVE_MORPH_TARGETS, draws with more weights are blended on the CPU
+uniform int morph_targets[8];
+uniform float morph_weights[8];
 uniform mat4 camera;
 
 layout(location = 0) in vec3 vert_pos;
 layout(location = 1) in vec3 vert_norm;
 layout(location = 2) in vec2 vert_tex;
+layout(location = 3) in vec4 vert_tangent;
+layout(location = 4) in vec2 vert_tex1;
+layout(location = 5) in uvec4 vert_joints;
+layout(location = 6) in vec4 vert_weights;
 
 out vec3 pos;
 out vec3 norm;
 out vec2 texcoord;
+out vec4 tangent;
+out vec2 texcoord1;
+
+mat4 joint_matrix(uint joint) {
+    int row = int(joint);
+    return mat4(
+        texelFetch(joint_sampler, ivec2(0, row), 0),
+        texelFetch(joint_sampler, ivec2(1, row), 0),
+        texelFetch(joint_sampler, ivec2(2, row), 0),
+        texelFetch(joint_sampler, ivec2(3, row), 0)
+    );
+}
+
+// Displacement of this vertex in one of the blocks of the morph texture
+vec3 morph_delta(int block) {
+    int width = textureSize(morph_sampler, 0).x;
+    ivec2 texel = ivec2(gl_VertexID % width, block * morph_rows + gl_VertexID / width);
+    return texelFetch(morph_sampler, texel, 0).xyz;
+}
 
 void main() {
-    gl_Position = camera * world * vec4(vert_pos, 1.0);
-    vec4 posT = world * vec4(vert_pos, 1.0);
+    vec3 position = vert_pos;
+    vec3 normal = vert_norm;
+    if(morphed) {
+        for(int i = 0; i < n_morph_weights; i++) {
+            position += morph_weights[i] * morph_delta(2 * morph_targets[i]);
+            normal += morph_weights[i] * morph_delta(2 * morph_targets[i] + 1);
+        }
+    }
+    mat4 model = world;
+    mat3 model_normal = world_normal;
+    if(skinned) {
+        model = vert_weights.x * joint_matrix(vert_joints.x)
+            + vert_weights.y * joint_matrix(vert_joints.y)
+            + vert_weights.z * joint_matrix(vert_joints.z)
+            + vert_weights.w * joint_matrix(vert_joints.w);
+        model_normal = transpose(inverse(mat3(model)));
+    }
+    gl_Position = camera * model * vec4(position, 1.0);
+    vec4 posT = model * vec4(position, 1.0);
     pos = vec3(posT) / posT.w;
-    norm = vec3(world * vec4(vert_norm, 0));
+    norm = model_normal * normal;
     texcoord = vert_tex;
+    texcoord1 = vert_tex1;
+    // Mirroring flips the bitangent along with the winding
+    float handedness = vert_tangent.w * sign(determinant(mat3(model)));
+    tangent = vec4(vec3(model * vec4(vert_tangent.xyz, 0)), handedness);
 }
@@ -5,6 +5,8 @@ precision mediump float;
 #define MAX_ITERATIONS 64
 
 uniform vec3 camera_pos;
+uniform vec3 camera_dir;
+uniform bool camera_orthographic;
 uniform mat4 camera;
 uniform sampler2D position_sampler;
 uniform sampler2D normal_sampler;
@@ -157,6 +159,9 @@ vec3 ray_march(vec3 pos, vec3 dir, float rough_factor) {
     float depth = texture(depth_sampler, coord).r;
     if(view.z >= depth) {
         float camdist = length(camera_pos - final);
+        if(camera_orthographic) {
+            camdist = dot(final - camera_pos, camera_dir);
+        }
         float dist = length(original_pos - final);
         float beta = pow(dist / camdist, 1.0 / log(camdist + 1.0));
         float alpha = 4.0 * pow(0.5 - rough_factor, 2.0);
@@ -176,7 +181,13 @@ void main() {
     float roughness = texture(roughness_sampler, tex_coord).r;
     float depth = texture(depth_sampler, tex_coord).r;
     vec3 ray_pos = position;
-    vec3 ray_dir = reflect(position - camera_pos, normal);
+    // Orthographic view rays share the camera direction, scaled like the
+    // perspective ones by the distance from the camera plane
+    vec3 incident = position - camera_pos;
+    if(camera_orthographic) {
+        incident = camera_dir * dot(incident, camera_dir);
+    }
+    vec3 ray_dir = reflect(incident, normal);
     vec3 out_color = ray_march(ray_pos, ray_dir, roughness);
     color_output = vec4(out_color, 1.0);
 }
@@ -0,0 +1,57 @@
+//! Draws the test assets in `scene/` offscreen, through EGL.
+#![cfg(all(feature = "app", target_os = "linux"))]
+
+use glad_gles2::gl;
+use scarlet::{try_import_scene_from_path, Application, ApplicationOptions};
+use std::os::raw::c_void;
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 90;
+
+/// Final frames of the asset at each of `times`.
+fn frames(app: &Application, asset: &str, times: &[f64]) -> Vec<Vec<u8>> {
+    let mut scene = try_import_scene_from_path(app.gl(), asset, WIDTH, HEIGHT)
+        .unwrap_or_else(|e| panic!("{}: {}", asset, e));
+    times
+        .iter()
+        .map(|&time| {
+            scene.draw_at("final", time);
+            let mut pixels = vec![0_u8; (WIDTH * HEIGHT * 4) as usize];
+            unsafe {
+                gl::ReadPixels(
+                    0,
+                    0,
+                    WIDTH as i32,
+                    HEIGHT as i32,
+                    gl::GL_RGBA,
+                    gl::GL_UNSIGNED_BYTE,
+                    pixels.as_mut_ptr() as *mut c_void,
+                );
+            }
+            pixels
+        })
+        .collect()
+}
+
+#[test]
+fn animated_assets_move() {
+    let mut options = ApplicationOptions::default();
+    options.headless = true;
+    options.width = WIDTH;
+    options.height = HEIGHT;
+    let app = Application::try_with_options(&options).expect("cannot create an EGL context");
+    // Rigged column bending at its middle joint
+    let skinned = frames(&app, "scene/skinned.glb", &[0.0, 1.0, 0.0]);
+    assert_ne!(skinned[0], skinned[1], "skinned.glb doesn't bend");
+    assert_eq!(
+        skinned[0], skinned[2],
+        "skinned.glb doesn't return to its bind pose"
+    );
+    // Ball stretching through its two targets, next to a still copy
+    let morph = frames(&app, "scene/morph.glb", &[0.0, 1.0, 0.0]);
+    assert_ne!(morph[0], morph[1], "morph.glb doesn't stretch");
+    assert_eq!(
+        morph[0], morph[2],
+        "morph.glb doesn't return to its rest shape"
+    );
+}